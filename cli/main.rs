@@ -1,90 +1,529 @@
+#[cfg(unix)]
+mod supervisor;
+
+use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
-use std::process::{Command, ExitStatus, Stdio};
-use std::str::FromStr;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use hide::{
+    EditFileMessage, FileTarget, FocusPaneMessage, LayoutEnvMessage, ListPanesMessage, Message,
+    NewInstanceMessage, PaneSummary, PaneType, Response, RevealFileMessage, SessionSummary,
+    WriteToPane, WriteToPaneMessage, WritesToPane, encode_pipe_message, parse_response,
+};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+// Exit codes, `run` exits with the status of its program instead.
+//
+//   1  the plugin answered with an error, or anything else failed
+//   2  invalid arguments, the code clap exits with as well
+//   3  zellij couldn't pipe the message to the plugin
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_ZELLIJ: i32 = 3;
+
+// Bytes read from stdin per write_to_pane message with `pipe --stdin`.
+const STDIN_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Drive the hide zellij plugin from the shell, keybindings and other programs
+#[derive(Parser)]
+#[clap(name = "hide-cli", version)]
+struct Cli {
+    /// Name the hide plugin is loaded under
+    #[clap(long, env = "PLUGIN_NAME", default_value = "hide", global = true)]
+    plugin: String,
+
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run a program in a pane, telling the plugin when it starts and exits
+    #[clap(trailing_var_arg = true)]
+    Run {
+        /// Start the program again when it crashes, waiting longer after every crash
+        #[clap(long)]
+        restart: bool,
+        /// The type of the pane, e.g. editor, terminal or file_explorer, whatever its title
+        #[clap(long, env = "HIDE_ROLE", value_parser = parse_pane_type)]
+        role: Option<PaneType>,
+        /// The program and its arguments
+        #[clap(required = true, allow_hyphen_values = true, value_name = "PROGRAM")]
+        command: Vec<String>,
+    },
+    /// Focus the pane of a type, e.g. editor, terminal, file_explorer or lazygit
+    Focus {
+        #[clap(value_name = "TYPE", value_parser = parse_pane_type)]
+        typ: PaneType,
+    },
+    /// Open files in the editor, at a line and column with path:line[:column]
+    Open {
+        #[clap(required = true, value_name = "PATH[:LINE[:COLUMN]]")]
+        paths: Vec<String>,
+        /// Don't focus the editor
+        #[clap(long)]
+        no_focus: bool,
+    },
+    /// Type keys into the pane of a type, e.g. `send terminal 'cargo test<enter>'`
+    Send {
+        #[clap(value_name = "TYPE", value_parser = parse_pane_type)]
+        typ: PaneType,
+        /// The keys, in the helix/vim notation of write_to_pane
+        #[clap(required_unless_present = "stdin", conflicts_with = "stdin")]
+        keys: Option<String>,
+        /// Type the keys as is, without looking for <key> symbols
+        #[clap(long)]
+        raw: bool,
+        /// Write stdin to the pane byte for byte instead of keys
+        #[clap(long, conflicts_with = "raw")]
+        stdin: bool,
+        /// Don't focus the pane
+        #[clap(long)]
+        no_focus: bool,
+    },
+    /// Make the file explorer navigate to a file and select it
+    Reveal {
+        path: PathBuf,
+        /// Don't focus the file explorer
+        #[clap(long)]
+        no_focus: bool,
+    },
+    /// Send a protocol message to the plugin, e.g. `pipe edit_file path=src/main.rs`
+    Pipe {
+        /// The message command, e.g. edit_file or write_to_pane
+        message: String,
+        /// The message arguments, only the first = separates the key from the value
+        #[clap(value_name = "KEY=VALUE", value_parser = parse_kv)]
+        args: Vec<(String, String)>,
+        /// Write stdin to the pane byte for byte, write_to_pane only
+        #[clap(long)]
+        stdin: bool,
+    },
+    /// Open a hide session for a project, or switch to the one already open
+    New {
+        /// The project, the current directory by default, and layout variables
+        #[clap(value_name = "PATH | var.NAME=VALUE", value_parser = parse_new_arg)]
+        args: Vec<NewArg>,
+        /// The layout to open the session with
+        #[clap(long)]
+        layout: Option<String>,
+        /// Open another session even if one is already open for the path
+        #[clap(long)]
+        force: bool,
+    },
+    /// List the hide sessions, the focused one is marked with *
+    Sessions {
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// List the panes of the hide sessions and the type of each pane
+    Panes {
+        /// Only list the panes of this session
+        #[clap(long)]
+        session: Option<u64>,
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print the completion script of a shell
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+}
 
-    if args.len() < 2 {
-        eprintln!("Usage: hide-cli <session_id> <command> [args...]");
-        std::process::exit(1);
+// NewArg is an argument of `new`, its path or a `var.NAME=VALUE` layout variable.
+#[derive(Clone)]
+enum NewArg {
+    Path(PathBuf),
+    Var(String, String),
+}
+
+// ExitError ends hide-cli with its exit code rather than the default EXIT_ERROR.
+#[derive(Debug)]
+struct ExitError {
+    code: i32,
+    message: String,
+}
+
+impl ExitError {
+    fn usage(message: impl Into<String>) -> Self {
+        ExitError {
+            code: EXIT_USAGE,
+            message: message.into(),
+        }
     }
+}
 
-    let cwd = env::current_dir()?;
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ExitError {}
 
-    let _session_id = env::var("SESSION_ID").unwrap_or_default();
-    let plugin_name = env::var("PLUGIN_NAME").unwrap_or("hide".to_string());
-    let command = &args[1];
-    let command_args = &args[2..];
-    let status = match command.as_str() {
-        "run" => run_command(&command_args)?,
-        "pipe" => pipe_command(&plugin_name, &command_args)?,
-        "new" => {
-            let path = command_args
-                .get(0)
-                .map(|s| PathBuf::from_str(s.as_str()))
-                .ok_or("invalid path".to_string())?
-                .unwrap_or(cwd);
+fn main() {
+    let cli = Cli::parse();
+
+    let code = match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("hide: {e}");
+            e.downcast_ref::<ExitError>().map_or(EXIT_ERROR, |e| e.code)
+        }
+    };
+
+    std::process::exit(code);
+}
 
-            let file_name = if path.is_file() {
-                path.parent().and_then(|f| f.file_name())
+fn run(cli: Cli) -> Result<i32> {
+    let plugin_name = cli.plugin.as_str();
+    match cli.command {
+        Commands::Run {
+            restart,
+            role,
+            command,
+        } => return run_supervised(plugin_name, restart, role, &command),
+        Commands::Focus { typ } => send_message(
+            plugin_name,
+            &Message::FocusPane(FocusPaneMessage { typ, session: None }),
+        )?,
+        Commands::Open { paths, no_focus } => {
+            let paths = paths
+                .iter()
+                .map(|path| absolute_target(path))
+                .collect::<Result<_>>()?;
+            let message = Message::EditFile(EditFileMessage {
+                paths,
+                session: None,
+                focus: no_focus.then_some(false),
+            });
+            send_message(plugin_name, &message)?
+        }
+        Commands::Send {
+            typ,
+            keys,
+            raw,
+            stdin,
+            no_focus,
+        } => {
+            let keys = keys.unwrap_or_default();
+            let data = if raw {
+                WritesToPane(vec![WriteToPane::String(keys)])
             } else {
-                path.file_name()
+                WritesToPane::try_from(keys.as_str()).map_err(ExitError::usage)?
+            };
+            let message = WriteToPaneMessage {
+                typ,
+                data,
+                session: None,
+                focus: no_focus.then_some(false),
             };
+            if stdin {
+                stream_stdin(plugin_name, message)?
+            } else {
+                send_message(plugin_name, &Message::WriteToPane(message))?
+            }
+        }
+        Commands::Reveal { path, no_focus } => {
+            let message = Message::RevealFile(RevealFileMessage {
+                path: absolute(&path)?.to_string_lossy().to_string(),
+                session: None,
+                focus: no_focus.then_some(false),
+            });
+            send_message(plugin_name, &message)?
+        }
+        Commands::Pipe {
+            message,
+            args,
+            stdin,
+        } => pipe_command(plugin_name, &message, &args, stdin)?,
+        Commands::New {
+            args,
+            layout,
+            force,
+        } => new_command(plugin_name, args, layout, force)?,
+        Commands::Sessions { json } => {
+            let sessions: Option<Vec<SessionSummary>> =
+                serde_json::from_value(request(plugin_name, &Message::ListSessions)?)?;
+            print_sessions(&sessions.unwrap_or_default(), json)?
+        }
+        Commands::Panes { session, json } => {
+            let message = Message::ListPanes(ListPanesMessage { session });
+            let panes: Option<Vec<PaneSummary>> =
+                serde_json::from_value(request(plugin_name, &message)?)?;
+            print_panes(&panes.unwrap_or_default(), json)?
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "hide-cli", &mut io::stdout());
+        }
+    }
+
+    Ok(0)
+}
 
-            let file_name = file_name
-                .ok_or(format!("invalid file path {path:?}"))
-                .map(|f| f.to_str().map(String::from))?
-                .ok_or("invalid path buf {path:?}".to_string())?;
+fn parse_kv(kv: &str) -> std::result::Result<(String, String), String> {
+    kv.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or(format!("{kv}: key=value pair required"))
+}
+
+fn parse_pane_type(typ: &str) -> std::result::Result<PaneType, String> {
+    if typ.trim().is_empty() {
+        return Err("a pane type is required, e.g. editor, terminal or file_explorer".into());
+    }
 
-            let path = path.to_string_lossy().to_string();
+    Ok(typ.into())
+}
+
+fn parse_new_arg(arg: &str) -> std::result::Result<NewArg, String> {
+    if !arg.starts_with("var.") {
+        return Ok(NewArg::Path(arg.into()));
+    }
+
+    let (k, v) = arg
+        .split_once('=')
+        .ok_or(format!("{arg}: var.<name>=value required"))?;
+    Ok(NewArg::Var(k.to_string(), v.to_string()))
+}
+
+// run_supervised is `run`, supervising the program needs unix signals.
+#[cfg(unix)]
+fn run_supervised(
+    plugin_name: &str,
+    restart: bool,
+    role: Option<PaneType>,
+    command: &[String],
+) -> Result<i32> {
+    let supervisor = supervisor::Supervisor {
+        plugin_name,
+        pane: supervisor::env_pane_id(),
+        role,
+        restart,
+    };
+    supervisor.run(command)
+}
 
-            pipe_command(&plugin_name, &[
-                "new_instance".into(),
-                format!("name={file_name}"),
-                format!("path={}", &path),
-            ])?
+#[cfg(not(unix))]
+fn run_supervised(
+    _plugin_name: &str,
+    _restart: bool,
+    _role: Option<PaneType>,
+    _command: &[String],
+) -> Result<i32> {
+    Err("run is only supported on unix".into())
+}
+
+fn new_command(
+    plugin_name: &str,
+    args: Vec<NewArg>,
+    layout: Option<String>,
+    force: bool,
+) -> Result<()> {
+    let mut path = None;
+    let mut vars = BTreeMap::new();
+    for arg in args {
+        match arg {
+            NewArg::Var(k, v) => {
+                vars.insert(k, v);
+            }
+            NewArg::Path(p) if path.is_none() => path = Some(p),
+            NewArg::Path(p) => {
+                return Err(ExitError::usage(format!(
+                    "unexpected path {}, new takes a single path",
+                    p.display()
+                ))
+                .into());
+            }
+        }
+    }
+
+    // the plugin recognises open projects by their absolute path
+    let path = match path {
+        Some(path) => path,
+        None => env::current_dir()?,
+    };
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("invalid path {}: {e}", path.display()))?;
+
+    // only the variables the layout uses leave the shell, the message is passed
+    // to zellij as an argument anyone can read with ps
+    let message = Message::LayoutEnv(LayoutEnvMessage {
+        layout: layout.clone(),
+    });
+    let names: Option<Vec<String>> = serde_json::from_value(request(plugin_name, &message)?)?;
+    for name in names.unwrap_or_default() {
+        if let Ok(value) = env::var(&name) {
+            vars.insert(format!("env.{name}"), value);
         }
-        _ => return Err("invalid command: {command}".into()),
+    }
+    vars.insert("git_branch".into(), git_branch(&path));
+
+    let file_name = if path.is_file() {
+        path.parent().and_then(|f| f.file_name())
+    } else {
+        path.file_name()
+    };
+
+    let file_name = file_name
+        .and_then(|f| f.to_str())
+        .map(String::from)
+        .ok_or(format!("invalid file path {path:?}"))?;
+
+    let path = path.to_string_lossy().to_string();
+
+    send_message(
+        plugin_name,
+        &Message::NewInstance(NewInstanceMessage {
+            name: file_name,
+            path,
+            layout,
+            vars,
+            force,
+        }),
+    )
+}
+
+// absolute resolves path against the current directory, the plugin and the
+// panes it writes to don't share it.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.into());
+    }
+
+    Ok(env::current_dir()?.join(path))
+}
+
+// absolute_target is absolute for a path:line[:column] argument, keeping its
+// position.
+fn absolute_target(target: &str) -> Result<FileTarget> {
+    let mut target = FileTarget::from(target);
+    target.path = absolute(Path::new(&target.path))?
+        .to_string_lossy()
+        .to_string();
+    Ok(target)
+}
+
+// git_branch returns the branch checked out at path, empty outside of a git
+// repository so layouts can use {git_branch} unconditionally.
+fn git_branch(path: &Path) -> String {
+    let dir = if path.is_file() {
+        path.parent().unwrap_or(path)
+    } else {
+        path
     };
 
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+fn pipe_command(
+    plugin_name: &str,
+    command: &str,
+    args: &[(String, String)],
+    stdin: bool,
+) -> Result<()> {
+    let kvs: Vec<(&str, &str)> = args.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+    if stdin {
+        return pipe_stdin(plugin_name, command, kvs);
+    }
+
+    let message = Message::from_kvs(command, &kvs).map_err(ExitError::usage)?;
+    send_message(plugin_name, &message)
+}
+
+// pipe_stdin is `pipe --stdin`, the data of the write_to_pane message is read
+// from stdin.
+fn pipe_stdin(plugin_name: &str, command: &str, mut kvs: Vec<(&str, &str)>) -> Result<()> {
+    if command != "write_to_pane" {
+        return Err(ExitError::usage(format!(
+            "--stdin is only supported by write_to_pane, not {command}"
+        ))
+        .into());
+    }
+    if kvs.iter().any(|(k, _)| *k == "data") {
+        return Err(ExitError::usage("data can't be used with --stdin").into());
+    }
+    kvs.push(("data", ""));
+
+    let Message::WriteToPane(message) =
+        Message::from_kvs(command, &kvs).map_err(ExitError::usage)?
+    else {
+        unreachable!("write_to_pane builds a WriteToPane message");
+    };
+
+    stream_stdin(plugin_name, message)
+}
+
+// stream_stdin writes everything read from stdin to the pane of message, as is,
+// in a write_to_pane message per chunk.
+fn stream_stdin(plugin_name: &str, message: WriteToPaneMessage) -> Result<()> {
+    let mut stdin = io::stdin().lock();
+    let mut chunk = vec![0; STDIN_CHUNK_SIZE];
+    loop {
+        let n = match stdin.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut message = message.clone();
+        message.data = WritesToPane(vec![WriteToPane::Bytes(chunk[..n].to_vec())]);
+        request(plugin_name, &Message::WriteToPane(message))?;
     }
 
     Ok(())
 }
 
-fn run_command(args: &[String]) -> Result<ExitStatus> {
-    let command = &args[0];
-    let command_args = &args[1..];
-    let mut cmd = Command::new(command);
-    cmd.current_dir(env::current_dir()?)
-        .envs(env::vars())
-        .args(command_args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
+fn send_message(plugin_name: &str, message: &Message) -> Result<()> {
+    let data = request(plugin_name, message)?;
+    if !data.is_null() {
+        println!("{}", serde_json::to_string_pretty(&data)?);
+    }
 
-    cmd.status().map_err(Into::into)
+    Ok(())
 }
 
-fn pipe_command(plugin_name: &str, args: &[String]) -> Result<ExitStatus> {
-    let mut message = String::new();
-    message.push('0'); // protocol version
-    message.push_str(&args[0]); // message type, e.g. edit_file
-    message.push(';');
-    for kv in &args[1..] {
-        // message args, e.g. path=/tmp
-        let mut split = kv.split('=');
-        let k = split.next().ok_or("key: keyvalue pair required")?;
-        let v = split.next().ok_or("value: keyvalue pair required")?;
+// request sends the message to the plugin and returns the data of its response,
+// failing with EXIT_ZELLIJ when zellij couldn't deliver it. Messages sent from a
+// hide pane act on its session rather than the focused one.
+fn request(plugin_name: &str, message: &Message) -> Result<serde_json::Value> {
+    request_within(plugin_name, message, None)
+}
 
-        message.push_str(&format!("{}={};", k, v));
+// request_within is request giving up after timeout, zellij waits for the plugin
+// for as long as it isn't loaded or hasn't been granted its permissions.
+fn request_within(
+    plugin_name: &str,
+    message: &Message,
+    timeout: Option<Duration>,
+) -> Result<serde_json::Value> {
+    let mut message = message.clone();
+    if let Some(session_id) = env_session_id()? {
+        message.set_default_session(session_id);
     }
+    let message = encode_pipe_message(&message)?;
 
     let mut cmd = Command::new("zellij");
     cmd.current_dir(env::current_dir()?)
@@ -95,9 +534,123 @@ fn pipe_command(plugin_name: &str, args: &[String]) -> Result<ExitStatus> {
         .arg(plugin_name)
         .arg("--")
         .arg(message)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
+        // the message is passed as an argument, stdin may be streamed by pipe --stdin
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
         .stderr(Stdio::inherit());
 
-    cmd.status().map_err(Into::into)
+    let zellij_error = |message: String| ExitError {
+        code: EXIT_ZELLIJ,
+        message,
+    };
+    let child = cmd
+        .spawn()
+        .map_err(|e| zellij_error(format!("running zellij: {e}")))?;
+    let output = match timeout {
+        None => child.wait_with_output(),
+        Some(timeout) => match output_within(child, timeout) {
+            Ok(Some(output)) => Ok(output),
+            Ok(None) => {
+                let message = format!("no answer from the {plugin_name} plugin within {timeout:?}");
+                return Err(zellij_error(message).into());
+            }
+            Err(e) => Err(e),
+        },
+    }
+    .map_err(|e| zellij_error(format!("running zellij: {e}")))?;
+    if !output.status.success() {
+        return Err(zellij_error(format!("zellij action pipe failed: {}", output.status)).into());
+    }
+
+    // The plugin answers with one response line per running instance.
+    let mut data = serde_json::Value::Null;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_response(line)? {
+            Response::Ok { data: d } => {
+                if !d.is_null() {
+                    data = d;
+                }
+            }
+            Response::Error { message } => return Err(message.into()),
+        }
+    }
+
+    Ok(data)
+}
+
+// output_within is wait_with_output for at most timeout, None when the child was
+// killed for running longer. Stdout isn't read until the child exits, it must fit
+// in the pipe's buffer the way a response does.
+fn output_within(mut child: Child, timeout: Duration) -> io::Result<Option<Output>> {
+    let deadline = Instant::now() + timeout;
+    while child.try_wait()?.is_none() {
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    child.wait_with_output().map(Some)
+}
+
+// env_session_id returns the SESSION_ID set for hide panes by their layout.
+fn env_session_id() -> Result<Option<u64>> {
+    let id = env::var("SESSION_ID").unwrap_or_default();
+    if id.is_empty() {
+        return Ok(None);
+    }
+
+    let id = id
+        .parse()
+        .map_err(|_| format!("invalid SESSION_ID {id}, expected a session id"))?;
+    Ok(Some(id))
+}
+
+fn print_sessions(sessions: &[SessionSummary], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(sessions)?);
+        return Ok(());
+    }
+
+    println!("  {:<16} {:<10} PANES", "SESSION", "TABS");
+    for session in sessions {
+        let focused = if session.focused { '*' } else { ' ' };
+        let tabs: Vec<String> = session.tabs.iter().map(usize::to_string).collect();
+        println!(
+            "{focused} {:<16} {:<10} {}",
+            session.id,
+            tabs.join(","),
+            session.panes
+        );
+    }
+
+    Ok(())
+}
+
+fn print_panes(panes: &[PaneSummary], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(panes)?);
+        return Ok(());
+    }
+
+    println!(
+        "  {:<16} {:<4} {:<6} {:<16} TITLE",
+        "SESSION", "TAB", "PANE", "TYPE"
+    );
+    for pane in panes {
+        let focused = if pane.focused { '*' } else { ' ' };
+        println!(
+            "{focused} {:<16} {:<4} {:<6} {:<16} {}",
+            pane.session,
+            pane.tab,
+            pane.id,
+            pane.typ.as_str(),
+            pane.title
+        );
+    }
+
+    Ok(())
 }
@@ -0,0 +1,286 @@
+use std::env;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hide::{Message, PaneExitedMessage, PaneType, RegisterPaneMessage};
+use libc::{SIGHUP, SIGINT, SIGTERM, SIGWINCH, c_int, siginfo_t};
+
+use crate::{EXIT_ERROR, Result, request_within};
+
+// The pid of the running program, 0 while there is none.
+static CHILD: AtomicI32 = AtomicI32::new(0);
+// Set once the supervisor is asked to stop, the program isn't restarted anymore.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+const FORWARDED_SIGNALS: [c_int; 4] = [SIGINT, SIGTERM, SIGHUP, SIGWINCH];
+// The signals asking the program to stop, it isn't restarted after them.
+const STOP_SIGNALS: [c_int; 3] = [SIGINT, SIGTERM, SIGHUP];
+
+// Restarts wait MIN_BACKOFF, doubled after every crash up to MAX_BACKOFF. A
+// program that ran for STABLE_RUN before crashing waits MIN_BACKOFF again.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const STABLE_RUN: Duration = Duration::from_secs(10);
+
+// How long the plugin gets to answer a notification. The program already
+// started, or exited, it doesn't wait for a plugin that isn't loaded.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Supervisor runs a program in a pane for `hide-cli run`. The plugin is told
+// about every start and exit of the program, and signals sent to hide-cli reach
+// the program.
+pub struct Supervisor<'a> {
+    pub plugin_name: &'a str,
+    // The pane the program runs in, panes are only registered inside zellij.
+    pub pane: Option<u32>,
+    // The type the plugin gives the pane instead of classifying it.
+    pub role: Option<PaneType>,
+    // Start the program again when it crashes.
+    pub restart: bool,
+}
+
+impl Supervisor<'_> {
+    // run runs the program until it exits without being restarted and returns
+    // its exit code.
+    pub fn run(&self, args: &[String]) -> Result<i32> {
+        let (command, command_args) = args.split_first().ok_or("a program is required")?;
+        forward_signals()?;
+
+        let mut backoff = Backoff::default();
+        loop {
+            let started = Instant::now();
+            let mut child = Command::new(command)
+                .current_dir(env::current_dir()?)
+                .envs(env::vars())
+                .args(command_args)
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .spawn()
+                .map_err(|e| format!("running {command}: {e}"))?;
+            CHILD.store(child.id() as i32, Ordering::SeqCst);
+            self.notify(|pane| {
+                Message::RegisterPane(RegisterPaneMessage {
+                    pane,
+                    pid: child.id(),
+                    role: self.role.clone(),
+                    session: None,
+                })
+            });
+
+            let status = child.wait();
+            CHILD.store(0, Ordering::SeqCst);
+            let status = status?;
+
+            let code = exit_code(status);
+            let restarting = self.restart && crashed(status) && !STOPPING.load(Ordering::SeqCst);
+            self.notify(|pane| {
+                Message::PaneExited(PaneExitedMessage {
+                    pane,
+                    status: code,
+                    restarting,
+                    session: None,
+                })
+            });
+            if !restarting {
+                return Ok(code);
+            }
+
+            let delay = backoff.delay(started.elapsed());
+            eprintln!("hide: {command} exited with {code}, restarting in {delay:?}");
+            if !wait_unless_stopping(delay) {
+                return Ok(code);
+            }
+        }
+    }
+
+    // notify sends the message built for the pane to the plugin. The program
+    // keeps running when the plugin can't be reached, or doesn't answer.
+    fn notify(&self, message: impl FnOnce(u32) -> Message) {
+        let Some(pane) = self.pane else {
+            return;
+        };
+        if let Err(e) = request_within(self.plugin_name, &message(pane), Some(NOTIFY_TIMEOUT)) {
+            eprintln!("hide: {e}");
+        }
+    }
+}
+
+// env_pane_id returns the id of the pane hide-cli runs in, zellij sets
+// ZELLIJ_PANE_ID for the commands of its panes.
+pub fn env_pane_id() -> Option<u32> {
+    env::var("ZELLIJ_PANE_ID").ok()?.parse().ok()
+}
+
+// Backoff is the wait before each restart of a crashing program.
+struct Backoff {
+    next: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff { next: MIN_BACKOFF }
+    }
+}
+
+impl Backoff {
+    // delay returns the wait before restarting a program that crashed after
+    // running for ran_for.
+    fn delay(&mut self, ran_for: Duration) -> Duration {
+        if ran_for >= STABLE_RUN {
+            self.next = MIN_BACKOFF;
+        }
+
+        let delay = self.next;
+        self.next = (delay * 2).min(MAX_BACKOFF);
+        delay
+    }
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    exit_code_of(status.code(), status.signal())
+}
+
+// exit_code_of returns the exit code of a program, 128 + the signal when it was
+// killed the way shells report it.
+fn exit_code_of(code: Option<i32>, signal: Option<c_int>) -> i32 {
+    code.or_else(|| signal.map(|signal| 128 + signal))
+        .unwrap_or(EXIT_ERROR)
+}
+
+fn crashed(status: ExitStatus) -> bool {
+    crashed_with(status.code(), status.signal())
+}
+
+// crashed_with tells a program that failed from one that was stopped, a program
+// killed by SIGINT, SIGTERM or SIGHUP was stopped on purpose. So was one that
+// handled them itself and exited with 128 + the signal, like shells do.
+fn crashed_with(code: Option<i32>, signal: Option<c_int>) -> bool {
+    let signal = signal.or_else(|| code.filter(|code| *code > 128).map(|code| code - 128));
+    match signal {
+        Some(signal) => !STOP_SIGNALS.contains(&signal),
+        None => code != Some(0),
+    }
+}
+
+// wait_unless_stopping sleeps for duration and returns false when the
+// supervisor was asked to stop meanwhile.
+fn wait_unless_stopping(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if STOPPING.load(Ordering::SeqCst) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    !STOPPING.load(Ordering::SeqCst)
+}
+
+fn forward_signals() -> Result<()> {
+    for signal in FORWARDED_SIGNALS {
+        // SAFETY: forward only touches atomics and calls kill, both are
+        // async-signal-safe.
+        unsafe {
+            signal_hook_registry::register_sigaction(signal, move |info| forward(signal, info))?;
+        }
+    }
+
+    Ok(())
+}
+
+// forward passes a signal on to the program. The terminal sends SIGINT and
+// SIGWINCH to its whole foreground process group, the program got those from
+// the kernel already and only signals sent by another process are forwarded.
+fn forward(signal: c_int, info: &siginfo_t) {
+    let child = CHILD.load(Ordering::SeqCst);
+    if matches!(signal, SIGTERM | SIGHUP) || (signal == SIGINT && child == 0) {
+        STOPPING.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: si_pid is set for signals sent with kill and 0 for the ones the
+    // kernel sends.
+    let sent_by_kernel = unsafe { info.si_pid() } == 0;
+    if child > 0 && !sent_by_kernel {
+        // SAFETY: kill is async-signal-safe.
+        unsafe {
+            libc::kill(child, signal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::{SIGKILL, SIGSEGV};
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::default();
+        let delays: Vec<u64> = (0..9)
+            .map(|_| backoff.delay(Duration::from_secs(1)).as_millis() as u64)
+            .collect();
+
+        assert_eq!(
+            delays,
+            vec![500, 1000, 2000, 4000, 8000, 16000, 30000, 30000, 30000]
+        );
+    }
+
+    #[test]
+    fn test_backoff_resets_after_stable_run() {
+        let mut backoff = Backoff::default();
+        backoff.delay(Duration::ZERO);
+        backoff.delay(Duration::ZERO);
+
+        assert_eq!(
+            backoff.delay(Duration::from_secs(9)),
+            Duration::from_secs(2)
+        );
+        assert_eq!(backoff.delay(STABLE_RUN), MIN_BACKOFF);
+        assert_eq!(backoff.delay(Duration::ZERO), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_exit_code_of() {
+        assert_eq!(exit_code_of(Some(0), None), 0);
+        assert_eq!(exit_code_of(Some(101), None), 101);
+        assert_eq!(exit_code_of(None, Some(SIGKILL)), 137);
+        assert_eq!(exit_code_of(None, Some(SIGINT)), 130);
+        assert_eq!(exit_code_of(None, None), EXIT_ERROR);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        // wait statuses: the exit code in the second byte, or the signal
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(ExitStatus::from_raw(SIGTERM)), 143);
+    }
+
+    #[test]
+    fn test_crashed_with() {
+        assert!(!crashed_with(Some(0), None));
+        assert!(crashed_with(Some(1), None));
+        assert!(crashed_with(None, Some(SIGKILL)));
+        assert!(crashed_with(None, Some(SIGSEGV)));
+        for signal in STOP_SIGNALS {
+            assert!(!crashed_with(None, Some(signal)));
+        }
+        assert!(crashed(ExitStatus::from_raw(SIGSEGV)));
+        assert!(!crashed(ExitStatus::from_raw(0)));
+    }
+
+    #[test]
+    fn test_crashed_with_exit_code_of_a_stop_signal() {
+        // programs handling SIGINT themselves exit with 130, like shells do
+        for code in [130, 143, 129] {
+            assert!(!crashed_with(Some(code), None));
+            assert!(!crashed(ExitStatus::from_raw(code << 8)));
+        }
+        assert!(crashed_with(Some(137), None));
+        assert!(crashed_with(Some(139), None));
+        assert!(crashed_with(Some(128), None));
+    }
+}
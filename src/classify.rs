@@ -0,0 +1,362 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use zellij_tile::prelude::PaneInfo;
+
+use crate::{PaneType, Result, extract_role_from_cmd};
+
+// PaneRules classify panes into a PaneType. Rules come from the `pane_rules`
+// plugin configuration, one per line:
+//
+//   <field> <pattern> <type>
+//
+// field is `title`, `name` or `command` (the pane's terminal command). Zellij
+// reports the name a pane was given as its title, `name` matches the same text
+// as `title`. pattern is a glob supporting `*` and `?` that matches case
+// insensitively, or `regex:` followed by a regular expression that matches
+// anywhere in the field. type is anything focus_pane accepts: editor,
+// file_explorer, terminal or a custom name. Empty lines and lines starting with
+// `#` are skipped.
+//
+// A role declared with HIDE_ROLE in the pane's command takes precedence over the
+// rules. Otherwise the first matching rule wins, panes no rule matches are
+// classified by their title the way PaneType::from does.
+#[derive(Debug, Default, PartialEq)]
+pub struct PaneRules {
+    rules: Vec<PaneRule>,
+}
+
+#[derive(Debug, PartialEq)]
+struct PaneRule {
+    field: RuleField,
+    pattern: Pattern,
+    typ: PaneType,
+}
+
+#[derive(Debug, PartialEq)]
+enum RuleField {
+    Title,
+    Command,
+}
+
+#[derive(Debug)]
+enum Pattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+const REGEX_PREFIX: &str = "regex:";
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self> {
+        match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(regex) => Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|e| format!("invalid regex {regex}: {e}")),
+            None => Ok(Pattern::Glob(pattern.into())),
+        }
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob_match(glob, text),
+            Pattern::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+// Regex has no PartialEq, patterns are the same when they were parsed from the
+// same source.
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Pattern::Glob(a), Pattern::Glob(b)) => a == b,
+            (Pattern::Regex(a), Pattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl PaneRules {
+    pub fn from_config(config: &BTreeMap<String, String>) -> Result<Self> {
+        match config.get("pane_rules") {
+            Some(rules) => PaneRules::parse(rules),
+            None => Ok(PaneRules::default()),
+        }
+    }
+
+    pub fn parse(rules: &str) -> Result<Self> {
+        let mut parsed = vec![];
+        for (idx, line) in rules.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                format!(
+                    "pane_rules line {}: expected <field> <pattern> <type>",
+                    idx + 1
+                )
+            };
+            let (field, rest) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (pattern, typ) = rest
+                .trim()
+                .rsplit_once(char::is_whitespace)
+                .ok_or_else(invalid)?;
+
+            let field = match field {
+                "title" | "name" => RuleField::Title,
+                "command" => RuleField::Command,
+                _ => {
+                    return Err(format!(
+                        "pane_rules line {}: invalid field {field}, valid: title, name, command",
+                        idx + 1
+                    ));
+                }
+            };
+            let pattern = Pattern::parse(pattern.trim())
+                .map_err(|e| format!("pane_rules line {}: {e}", idx + 1))?;
+
+            parsed.push(PaneRule {
+                field,
+                pattern,
+                typ: typ.into(),
+            });
+        }
+
+        Ok(PaneRules { rules: parsed })
+    }
+
+    pub fn classify(&self, info: &PaneInfo) -> PaneType {
+        if let Some(role) = info
+            .terminal_command
+            .as_deref()
+            .and_then(extract_role_from_cmd)
+        {
+            return role;
+        }
+
+        self.rules
+            .iter()
+            .find(|rule| {
+                let value = match rule.field {
+                    RuleField::Title => Some(info.title.as_str()),
+                    RuleField::Command => info.terminal_command.as_deref(),
+                };
+                value.is_some_and(|value| rule.pattern.is_match(value))
+            })
+            .map(|rule| rule.typ.clone())
+            .unwrap_or_else(|| info.title.as_str().into())
+    }
+}
+
+// glob_match matches text against a pattern where `*` is any number of characters
+// and `?` a single one, ignoring case.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    p = star + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(title: &str, terminal_command: Option<&str>) -> PaneInfo {
+        PaneInfo {
+            title: title.into(),
+            terminal_command: terminal_command.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("hx", "HX"));
+        assert!(glob_match("*nvim*", "~/src: nvim main.rs"));
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(glob_match("b?oot", "broot"));
+        assert!(glob_match("a*b*c", "aXXbYYbc"));
+        assert!(glob_match("ünï*", "ÜNÏCODE"));
+        assert!(!glob_match("*.rs", "main.rs.bak"));
+        assert!(!glob_match("b?oot", "boot"));
+        assert!(!glob_match("hx", "hx ."));
+    }
+
+    #[test]
+    fn test_pane_rules_classify() {
+        let rules = PaneRules::parse(
+            "
+            # nvim when helix is broken
+            title *nvim* editor
+            command *broot* file_explorer
+            command nu -c SESSION_ID=* nu terminal
+            title Git ui lazygit
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.classify(&pane("nvim main.rs", None)),
+            PaneType::Editor
+        );
+        assert_eq!(
+            rules.classify(&pane("~/src", Some("fish -c SESSION_ID=1 broot"))),
+            PaneType::FileExplorer
+        );
+        assert_eq!(
+            rules.classify(&pane("~/src", Some("nu -c SESSION_ID=1 nu"))),
+            PaneType::Terminal
+        );
+        assert_eq!(
+            rules.classify(&pane("git UI", None)),
+            PaneType::Custom("lazygit".into())
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_role_first() {
+        let rules = PaneRules::parse("command *hx* terminal").unwrap();
+
+        assert_eq!(
+            rules.classify(&pane("yazi", Some("fish -c HIDE_ROLE=editor hx"))),
+            PaneType::Editor
+        );
+        assert_eq!(
+            rules.classify(&pane("yazi", Some("fish -c hx"))),
+            PaneType::Terminal
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_fall_back_to_title() {
+        let rules = PaneRules::parse("title *nvim* editor").unwrap();
+
+        assert_eq!(rules.classify(&pane("yazi", None)), PaneType::FileExplorer);
+        assert_eq!(
+            PaneRules::default().classify(&pane("Editor", None)),
+            PaneType::Editor
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_first_match_wins() {
+        let rules = PaneRules::parse("title hx* terminal\ntitle hx editor").unwrap();
+
+        assert_eq!(rules.classify(&pane("hx", None)), PaneType::Terminal);
+    }
+
+    #[test]
+    fn test_pane_rules_regex() {
+        let rules = PaneRules::parse(
+            r"
+            title regex:^n?vim\b editor
+            command regex:(?i)SESSION_ID=\d+ (nu|fish)$ terminal
+            name regex:^Git( ui)?$ lazygit
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.classify(&pane("nvim main.rs", None)),
+            PaneType::Editor
+        );
+        assert_eq!(rules.classify(&pane("vim", None)), PaneType::Editor);
+        assert_eq!(
+            rules.classify(&pane("nvimpager", None)),
+            PaneType::Custom("nvimpager".into())
+        );
+        assert_eq!(
+            rules.classify(&pane("~/src", Some("sh -c session_id=12 fish"))),
+            PaneType::Terminal
+        );
+        assert_eq!(
+            rules.classify(&pane("Git ui", None)),
+            PaneType::Custom("lazygit".into())
+        );
+        // regexes are case sensitive unless they ask otherwise
+        assert_eq!(
+            rules.classify(&pane("git", None)),
+            PaneType::Custom("git".into())
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_name() {
+        let rules = PaneRules::parse("name Tests terminal").unwrap();
+
+        assert_eq!(rules.classify(&pane("tests", None)), PaneType::Terminal);
+    }
+
+    #[test]
+    fn test_pane_rules_invalid_regex() {
+        let rules = PaneRules::parse("title regex:(hx editor");
+
+        assert!(
+            rules
+                .err()
+                .unwrap()
+                .starts_with("pane_rules line 1: invalid regex (hx: ")
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_invalid_field() {
+        let rules = PaneRules::parse("\npid hx editor");
+
+        assert_eq!(
+            rules.err().unwrap(),
+            "pane_rules line 2: invalid field pid, valid: title, name, command".to_string()
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_missing_type() {
+        let rules = PaneRules::parse("title hx");
+
+        assert_eq!(
+            rules.err().unwrap(),
+            "pane_rules line 1: expected <field> <pattern> <type>".to_string()
+        );
+    }
+
+    #[test]
+    fn test_pane_rules_from_config() {
+        let config = BTreeMap::from([("pane_rules".to_string(), "title nvim editor".to_string())]);
+        let rules = PaneRules::from_config(&config).unwrap();
+
+        assert_eq!(rules.classify(&pane("nvim", None)), PaneType::Editor);
+        assert_eq!(
+            PaneRules::from_config(&BTreeMap::new()).unwrap(),
+            PaneRules::default()
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use zellij_tile::prelude::{BareKey, KeyWithModifier};
+
+use crate::{
+    InstancePane, PaneSummary, PickerAction, ProjectPicker, SessionSummary, summarize_panes,
+    summarize_sessions,
+};
+
+pub(crate) const RESET: &str = "\u{1b}[0m";
+pub(crate) const BOLD: &str = "\u{1b}[1m";
+pub(crate) const DIM: &str = "\u{1b}[2m";
+pub(crate) const REVERSE: &str = "\u{1b}[7m";
+
+const HELP: &str = "↑/k ↓/j move  enter focus  n open project  esc close";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DashboardRow {
+    Session(SessionSummary),
+    Pane(PaneSummary),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DashboardAction {
+    Render,
+    FocusPane(u32),
+    ScanProjects,
+    OpenProject(String),
+    Close,
+}
+
+// Dashboard is the session switcher shown when the plugin runs in a visible pane.
+// It lists every session followed by its panes, picking a session focuses the
+// pane that was last focused in it. `n` switches to the project picker.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    selected: usize,
+    picker: Option<ProjectPicker>,
+}
+
+impl Dashboard {
+    pub fn rows(
+        instances: &HashMap<u64, Vec<InstancePane>>,
+        focused_session: u64,
+    ) -> Vec<DashboardRow> {
+        let mut rows = vec![];
+        for session in summarize_sessions(instances, focused_session) {
+            let panes = summarize_panes(instances, Some(session.id)).unwrap_or_default();
+            rows.push(DashboardRow::Session(session));
+            rows.extend(panes.into_iter().map(DashboardRow::Pane));
+        }
+        rows
+    }
+
+    pub fn handle_key(
+        &mut self,
+        key: &KeyWithModifier,
+        rows: &[DashboardRow],
+    ) -> Option<DashboardAction> {
+        if let Some(picker) = &mut self.picker {
+            return match picker.handle_key(key)? {
+                PickerAction::Render => Some(DashboardAction::Render),
+                PickerAction::Open(path) => {
+                    self.picker = None;
+                    Some(DashboardAction::OpenProject(path))
+                }
+                PickerAction::Close => {
+                    self.picker = None;
+                    Some(DashboardAction::Render)
+                }
+            };
+        }
+
+        if !key.has_no_modifiers() {
+            return None;
+        }
+
+        match key.bare_key {
+            BareKey::Down | BareKey::Char('j') => {
+                self.selected = (self.selected + 1).min(rows.len().saturating_sub(1));
+                Some(DashboardAction::Render)
+            }
+            BareKey::Up | BareKey::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                Some(DashboardAction::Render)
+            }
+            BareKey::Enter => self.focus_target(rows).map(DashboardAction::FocusPane),
+            BareKey::Char('n') => {
+                self.picker = Some(ProjectPicker::default());
+                Some(DashboardAction::ScanProjects)
+            }
+            BareKey::Esc | BareKey::Char('q') => Some(DashboardAction::Close),
+            _ => None,
+        }
+    }
+
+    // set_projects hands the scanned project directories to the picker, results of
+    // a scan the picker was closed for are dropped.
+    pub fn set_projects(&mut self, projects: Vec<String>) {
+        if let Some(picker) = &mut self.picker {
+            picker.set_projects(projects);
+        }
+    }
+
+    fn focus_target(&self, rows: &[DashboardRow]) -> Option<u32> {
+        match rows.get(self.selected)? {
+            DashboardRow::Pane(pane) => Some(pane.id),
+            DashboardRow::Session(session) => {
+                let panes = rows.iter().filter_map(|row| match row {
+                    DashboardRow::Pane(pane) if pane.session == session.id => Some(pane),
+                    _ => None,
+                });
+                let mut first = None;
+                for pane in panes {
+                    if pane.focused {
+                        return Some(pane.id);
+                    }
+                    first.get_or_insert(pane.id);
+                }
+                first
+            }
+        }
+    }
+
+    // render returns the lines to print in a pane of the given size, scrolled so
+    // the selected row is visible.
+    pub fn render(&mut self, rows: &[DashboardRow], height: usize, width: usize) -> Vec<String> {
+        if let Some(picker) = &mut self.picker {
+            return picker.render(height, width);
+        }
+
+        self.selected = self.selected.min(rows.len().saturating_sub(1));
+
+        let mut lines = vec![format!("{BOLD}{}{RESET}", truncate("hide sessions", width))];
+        if rows.is_empty() {
+            lines.push(format!("{DIM}{}{RESET}", truncate("no sessions", width)));
+        }
+
+        // title and help take a line each
+        let visible = height.saturating_sub(2).max(1);
+        let offset = (self.selected + 1).saturating_sub(visible);
+        for (idx, row) in rows.iter().enumerate().skip(offset).take(visible) {
+            let (text, style) = match row {
+                DashboardRow::Session(session) => {
+                    let tabs: Vec<String> = session.tabs.iter().map(usize::to_string).collect();
+                    let text = format!(
+                        "{} {}  tabs {}  {} panes",
+                        if session.focused { '*' } else { ' ' },
+                        session.id,
+                        tabs.join(","),
+                        session.panes
+                    );
+                    (text, if session.focused { BOLD } else { "" })
+                }
+                DashboardRow::Pane(pane) => {
+                    let text = format!("    {:<14} {}", pane.typ.as_str(), pane.title);
+                    (text, "")
+                }
+            };
+
+            let text = truncate(&text, width);
+            if idx == self.selected {
+                lines.push(format!("{style}{REVERSE}{text}{RESET}"));
+            } else {
+                lines.push(format!("{style}{text}{RESET}"));
+            }
+        }
+
+        if height > 1 {
+            lines.push(format!("{DIM}{}{RESET}", truncate(HELP, width)));
+        }
+        lines
+    }
+}
+
+pub(crate) fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zellij_tile::prelude::PaneInfo;
+
+    fn pane(id: u32, title: &str, is_focused: bool) -> InstancePane {
+        InstancePane {
+            info: PaneInfo {
+                id,
+                title: title.into(),
+                is_focused,
+                ..Default::default()
+            },
+            tab_index: 0,
+            typ: title.into(),
+        }
+    }
+
+    fn rows() -> Vec<DashboardRow> {
+        let instances = HashMap::from([
+            (1, vec![pane(1, "hx", false), pane(2, "yazi", true)]),
+            (
+                2,
+                vec![pane(3, "Editor", false), pane(4, "Terminal", false)],
+            ),
+        ]);
+        Dashboard::rows(&instances, 2)
+    }
+
+    fn key(bare_key: BareKey) -> KeyWithModifier {
+        KeyWithModifier::new(bare_key)
+    }
+
+    #[test]
+    fn test_dashboard_rows() {
+        let rows = rows();
+
+        let kinds: Vec<String> = rows
+            .iter()
+            .map(|row| match row {
+                DashboardRow::Session(s) => format!("session {}", s.id),
+                DashboardRow::Pane(p) => format!("pane {}", p.id),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "session 1",
+                "pane 1",
+                "pane 2",
+                "session 2",
+                "pane 3",
+                "pane 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dashboard_navigation() {
+        let rows = rows();
+        let mut dashboard = Dashboard::default();
+
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Up), &rows),
+            Some(DashboardAction::Render)
+        );
+        assert_eq!(dashboard.selected, 0);
+
+        for _ in 0..10 {
+            dashboard.handle_key(&key(BareKey::Char('j')), &rows);
+        }
+        assert_eq!(dashboard.selected, 5);
+
+        dashboard.handle_key(&key(BareKey::Char('k')), &rows);
+        assert_eq!(dashboard.selected, 4);
+
+        assert_eq!(dashboard.handle_key(&key(BareKey::Char('x')), &rows), None);
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Esc), &rows),
+            Some(DashboardAction::Close)
+        );
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Down).with_ctrl_modifier(), &rows),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dashboard_focus_pane() {
+        let rows = rows();
+        let mut dashboard = Dashboard {
+            selected: 4,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Enter), &rows),
+            Some(DashboardAction::FocusPane(3))
+        );
+    }
+
+    #[test]
+    fn test_dashboard_focus_session() {
+        let rows = rows();
+
+        // session 1 focuses its last focused pane, session 2 its first one
+        let mut dashboard = Dashboard {
+            selected: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Enter), &rows),
+            Some(DashboardAction::FocusPane(2))
+        );
+
+        let mut dashboard = Dashboard {
+            selected: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Enter), &rows),
+            Some(DashboardAction::FocusPane(3))
+        );
+    }
+
+    #[test]
+    fn test_dashboard_focus_without_sessions() {
+        let mut dashboard = Dashboard::default();
+
+        assert_eq!(dashboard.handle_key(&key(BareKey::Enter), &[]), None);
+    }
+
+    #[test]
+    fn test_dashboard_project_picker() {
+        let rows = rows();
+        let mut dashboard = Dashboard {
+            selected: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Char('n')), &rows),
+            Some(DashboardAction::ScanProjects)
+        );
+        dashboard.set_projects(vec!["/src/hide".into(), "/src/zellij".into()]);
+
+        // keys go to the picker while it's open
+        dashboard.handle_key(&key(BareKey::Char('z')), &rows);
+        assert_eq!(
+            dashboard.render(&rows, 10, 80)[0],
+            format!("{BOLD}open project: z{RESET}")
+        );
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Enter), &rows),
+            Some(DashboardAction::OpenProject("/src/zellij".into()))
+        );
+        assert!(dashboard.picker.is_none());
+        assert_eq!(dashboard.selected, 2);
+    }
+
+    #[test]
+    fn test_dashboard_project_picker_close() {
+        let rows = rows();
+        let mut dashboard = Dashboard::default();
+
+        dashboard.handle_key(&key(BareKey::Char('n')), &rows);
+        assert_eq!(
+            dashboard.handle_key(&key(BareKey::Esc), &rows),
+            Some(DashboardAction::Render)
+        );
+        assert!(dashboard.picker.is_none());
+
+        // a scan finishing after the picker closed is ignored
+        dashboard.set_projects(vec!["/src/hide".into()]);
+        assert!(dashboard.picker.is_none());
+    }
+
+    #[test]
+    fn test_dashboard_render() {
+        let rows = rows();
+        let mut dashboard = Dashboard {
+            selected: 3,
+            ..Default::default()
+        };
+        let lines = dashboard.render(&rows, 20, 80);
+
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[1], format!("  1  tabs 0  2 panes{RESET}"));
+        assert_eq!(
+            lines[4],
+            format!("{BOLD}{REVERSE}* 2  tabs 0  2 panes{RESET}")
+        );
+        assert_eq!(lines[5], format!("    editor         Editor{RESET}"));
+        assert_eq!(lines[7], format!("{DIM}{HELP}{RESET}"));
+    }
+
+    #[test]
+    fn test_dashboard_render_scrolls_to_selection() {
+        let rows = rows();
+        let mut dashboard = Dashboard {
+            selected: 5,
+            ..Default::default()
+        };
+        let lines = dashboard.render(&rows, 4, 10);
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "    editor".to_string() + RESET);
+        assert_eq!(lines[2], format!("{REVERSE}    termin{RESET}"));
+    }
+
+    #[test]
+    fn test_dashboard_render_clamps_selection() {
+        let mut dashboard = Dashboard {
+            selected: 5,
+            ..Default::default()
+        };
+        let lines = dashboard.render(&[], 10, 80);
+
+        assert_eq!(dashboard.selected, 0);
+        assert_eq!(lines[1], format!("{DIM}no sessions{RESET}"));
+    }
+}
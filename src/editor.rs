@@ -0,0 +1,375 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FileTarget, Result, WriteToPane};
+
+// EditorAction is what hide can ask of the editor pane, each Editor turns it into
+// the keys to type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum EditorAction {
+    Open {
+        paths: Vec<FileTarget>,
+    },
+    Goto {
+        line: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        column: Option<u32>,
+    },
+    SaveAll,
+    ReloadAll,
+}
+
+pub trait Editor {
+    // keys returns what to write to the editor pane to perform action. Every
+    // sequence starts from normal mode, whatever mode the editor was left in.
+    fn keys(&self, action: &EditorAction) -> Vec<WriteToPane>;
+}
+
+pub struct Helix;
+
+impl Editor for Helix {
+    fn keys(&self, action: &EditorAction) -> Vec<WriteToPane> {
+        match action {
+            // a single :open takes every path, at the position when given
+            EditorAction::Open { paths } => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| quote_helix(&path.to_string()))
+                    .collect();
+                command(&format!(":o {}", paths.join(" ")))
+            }
+            EditorAction::Goto { line, column } => {
+                let mut keys = command(&format!(":goto {line}"));
+                // :goto lands on the first column, move right from the line start
+                if let Some(column) = column.filter(|c| *c > 1) {
+                    keys.push(WriteToPane::String(format!("gh{}l", column - 1)));
+                }
+                keys
+            }
+            EditorAction::SaveAll => command(":write-all"),
+            EditorAction::ReloadAll => command(":reload-all"),
+        }
+    }
+}
+
+// Vim drives both vim and neovim.
+pub struct Vim;
+
+impl Editor for Vim {
+    fn keys(&self, action: &EditorAction) -> Vec<WriteToPane> {
+        match action {
+            // one :edit per path, the last one ends up in the window
+            EditorAction::Open { paths } => paths
+                .iter()
+                .flat_map(|path| {
+                    let mut keys = command(&format!(":edit {}", escape_vim_path(&path.path)));
+                    if let Some(line) = path.line {
+                        keys.extend(command(&vim_cursor(line, path.column)).into_iter().skip(1));
+                    }
+                    keys
+                })
+                .collect(),
+            EditorAction::Goto { line, column } => command(&vim_cursor(*line, *column)),
+            EditorAction::SaveAll => command(":wall"),
+            // reloads the buffers changed on disk, vim asks about modified ones
+            EditorAction::ReloadAll => command(":checktime"),
+        }
+    }
+}
+
+pub struct Kakoune;
+
+impl Editor for Kakoune {
+    fn keys(&self, action: &EditorAction) -> Vec<WriteToPane> {
+        match action {
+            EditorAction::Open { paths } => paths
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, path)| {
+                    let mut edit = format!(":edit {}", quote_kakoune(&path.path));
+                    if let Some(line) = path.line {
+                        edit.push_str(&format!(" {line}"));
+                        if let Some(column) = path.column {
+                            edit.push_str(&format!(" {column}"));
+                        }
+                    }
+                    // only the first command needs to leave insert mode
+                    command(&edit)
+                        .into_iter()
+                        .skip(if idx == 0 { 0 } else { 1 })
+                })
+                .collect(),
+            EditorAction::Goto { line, column } => {
+                let column = column.unwrap_or(1);
+                command(&format!(":select {line}.{column},{line}.{column}"))
+            }
+            EditorAction::SaveAll => command(":write-all"),
+            EditorAction::ReloadAll => command(":evaluate-commands -buffer * edit!"),
+        }
+    }
+}
+
+// Editors picks the editor of a session. Editors are configured in the plugin
+// configuration:
+//
+//   editor           helix (default), neovim, vim or kakoune
+//   editor.<layout>  editor of the sessions opened with <layout>
+pub struct Editors {
+    default: Box<dyn Editor>,
+    layouts: BTreeMap<String, Box<dyn Editor>>,
+}
+
+impl Default for Editors {
+    fn default() -> Self {
+        Editors {
+            default: Box::new(Helix),
+            layouts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Editors {
+    pub fn from_config(config: &BTreeMap<String, String>) -> Result<Self> {
+        let mut editors = Editors::default();
+        for (key, name) in config {
+            if key == "editor" {
+                editors.default = editor_from_name(name)?;
+            } else if let Some(layout) = key.strip_prefix("editor.") {
+                editors
+                    .layouts
+                    .insert(layout.into(), editor_from_name(name)?);
+            }
+        }
+
+        Ok(editors)
+    }
+
+    // get returns the editor of sessions opened with layout, sessions hide didn't
+    // open get the default one.
+    pub fn get(&self, layout: Option<&str>) -> &dyn Editor {
+        layout
+            .and_then(|layout| self.layouts.get(layout))
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
+
+pub fn editor_from_name(name: &str) -> Result<Box<dyn Editor>> {
+    match name {
+        "helix" | "hx" => Ok(Box::new(Helix)),
+        "neovim" | "nvim" | "vim" | "vi" => Ok(Box::new(Vim)),
+        "kakoune" | "kak" => Ok(Box::new(Kakoune)),
+        _ => Err(format!(
+            "invalid editor {name}, valid: helix, neovim, vim, kakoune"
+        )),
+    }
+}
+
+// command returns the keys to run a `:` command from normal mode.
+fn command(command: &str) -> Vec<WriteToPane> {
+    vec![
+        WriteToPane::Escape,
+        WriteToPane::String(command.into()),
+        WriteToPane::Enter,
+    ]
+}
+
+// quote_helix quotes paths with whitespace, quotes or backslashes for helix's
+// command line, escaping the quotes and backslashes inside.
+fn quote_helix(path: &str) -> String {
+    if !path.contains(|ch: char| ch.is_whitespace() || matches!(ch, '"' | '\\')) {
+        return path.into();
+    }
+
+    let path = path.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{path}\"")
+}
+
+// escape_vim_path escapes path for :edit the way fnameescape() does, so wildcards,
+// `$VAR`s, `%` and `#` aren't expanded and `|` doesn't start another command. `~`
+// is escaped as well, a leading one would be the home directory.
+fn escape_vim_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    // a leading + is a command to run, and a lone - the previous file
+    if path.starts_with(['+', '>']) || path == "-" {
+        escaped.push('\\');
+    }
+    for ch in path.chars() {
+        if ch.is_whitespace()
+            || matches!(
+                ch,
+                '*' | '?'
+                    | '['
+                    | '{'
+                    | '`'
+                    | '$'
+                    | '\\'
+                    | '%'
+                    | '#'
+                    | '\''
+                    | '"'
+                    | '|'
+                    | '!'
+                    | '<'
+                    | '~'
+            )
+        {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn vim_cursor(line: u32, column: Option<u32>) -> String {
+    format!(":call cursor({line}, {})", column.unwrap_or(1))
+}
+
+fn quote_kakoune(path: &str) -> String {
+    format!("'{}'", path.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(paths: &[&str]) -> EditorAction {
+        EditorAction::Open {
+            paths: paths.iter().map(|path| FileTarget::from(*path)).collect(),
+        }
+    }
+
+    fn typed(keys: Vec<WriteToPane>) -> String {
+        keys.iter()
+            .map(|key| match key {
+                WriteToPane::Escape => "<esc>".to_string(),
+                WriteToPane::Enter => "<enter>".to_string(),
+                WriteToPane::String(s) => s.clone(),
+                WriteToPane::Bytes(b) => format!("{b:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_helix_keys() {
+        assert_eq!(
+            typed(Helix.keys(&open(&["src/main.rs:10:5", "/tmp/a b.txt"]))),
+            r#"<esc>:o src/main.rs:10:5 "/tmp/a b.txt"<enter>"#
+        );
+        assert_eq!(
+            typed(Helix.keys(&open(&[r#"/tmp/"a"\b.txt"#]))),
+            r#"<esc>:o "/tmp/\"a\"\\b.txt"<enter>"#
+        );
+        assert_eq!(
+            typed(Helix.keys(&EditorAction::Goto {
+                line: 3,
+                column: Some(7)
+            })),
+            "<esc>:goto 3<enter>gh6l"
+        );
+        assert_eq!(
+            typed(Helix.keys(&EditorAction::Goto {
+                line: 3,
+                column: Some(1)
+            })),
+            "<esc>:goto 3<enter>"
+        );
+        assert_eq!(
+            typed(Helix.keys(&EditorAction::SaveAll)),
+            "<esc>:write-all<enter>"
+        );
+        assert_eq!(
+            typed(Helix.keys(&EditorAction::ReloadAll)),
+            "<esc>:reload-all<enter>"
+        );
+    }
+
+    #[test]
+    fn test_escape_vim_path() {
+        assert_eq!(
+            escape_vim_path("/tmp/*?[a]{b}$HOME~%#|!<'`x`\"y\".txt"),
+            r#"/tmp/\*\?\[a]\{b}\$HOME\~\%\#\|\!\<\'\`x\`\"y\".txt"#
+        );
+        assert_eq!(escape_vim_path("~/a b\\c"), r"\~/a\ b\\c");
+        assert_eq!(escape_vim_path("+cmd"), r"\+cmd");
+        assert_eq!(escape_vim_path(">out"), r"\>out");
+        assert_eq!(escape_vim_path("-"), r"\-");
+        assert_eq!(escape_vim_path("a-b+c.rs"), "a-b+c.rs");
+    }
+
+    #[test]
+    fn test_vim_keys() {
+        assert_eq!(
+            typed(Vim.keys(&open(&["src/main.rs:10:5", "/tmp/a b%.txt"]))),
+            "<esc>:edit src/main.rs<enter>:call cursor(10, 5)<enter>\
+             <esc>:edit /tmp/a\\ b\\%.txt<enter>"
+        );
+        assert_eq!(
+            typed(Vim.keys(&EditorAction::Goto {
+                line: 3,
+                column: None
+            })),
+            "<esc>:call cursor(3, 1)<enter>"
+        );
+        assert_eq!(typed(Vim.keys(&EditorAction::SaveAll)), "<esc>:wall<enter>");
+        assert_eq!(
+            typed(Vim.keys(&EditorAction::ReloadAll)),
+            "<esc>:checktime<enter>"
+        );
+    }
+
+    #[test]
+    fn test_kakoune_keys() {
+        assert_eq!(
+            typed(Kakoune.keys(&open(&["src/main.rs:10:5", "it's.txt:2"]))),
+            "<esc>:edit 'src/main.rs' 10 5<enter>:edit 'it''s.txt' 2<enter>"
+        );
+        assert_eq!(
+            typed(Kakoune.keys(&EditorAction::Goto {
+                line: 3,
+                column: Some(7)
+            })),
+            "<esc>:select 3.7,3.7<enter>"
+        );
+        assert_eq!(
+            typed(Kakoune.keys(&EditorAction::ReloadAll)),
+            "<esc>:evaluate-commands -buffer * edit!<enter>"
+        );
+    }
+
+    #[test]
+    fn test_editors_from_config() {
+        let config = BTreeMap::from([
+            ("editor".to_string(), "nvim".to_string()),
+            ("editor.kak".to_string(), "kakoune".to_string()),
+        ]);
+        let editors = Editors::from_config(&config).unwrap();
+        let save_all = |layout| typed(editors.get(layout).keys(&EditorAction::SaveAll));
+
+        assert_eq!(save_all(None), "<esc>:wall<enter>");
+        assert_eq!(save_all(Some("default")), "<esc>:wall<enter>");
+        assert_eq!(save_all(Some("kak")), "<esc>:write-all<enter>");
+    }
+
+    #[test]
+    fn test_editors_default_to_helix() {
+        let editors = Editors::default();
+
+        assert_eq!(
+            typed(editors.get(Some("nu")).keys(&EditorAction::SaveAll)),
+            "<esc>:write-all<enter>"
+        );
+    }
+
+    #[test]
+    fn test_editors_invalid_editor() {
+        let config = BTreeMap::from([("editor.nu".to_string(), "emacs".to_string())]);
+
+        assert_eq!(
+            Editors::from_config(&config).err().unwrap(),
+            "invalid editor emacs, valid: helix, neovim, vim, kakoune".to_string()
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use crate::{Result, WriteToPane};
+
+// ExplorerCommand is how a file explorer is told what to do, either keys typed
+// into its pane or a command run on the host talking to it over its own IPC.
+#[derive(Debug, PartialEq)]
+pub enum ExplorerCommand {
+    Keys(Vec<WriteToPane>),
+    Run(Vec<String>),
+}
+
+pub trait Explorer {
+    // reveal returns how to make the explorer of session_id navigate to path and
+    // select it.
+    fn reveal(&self, path: &str, session_id: u64) -> ExplorerCommand;
+}
+
+// Yazi is revealed through `ya emit-to`, it has to be started with
+// `--client-id={session_id}` the way the default layout does.
+pub struct Yazi;
+
+impl Explorer for Yazi {
+    fn reveal(&self, path: &str, session_id: u64) -> ExplorerCommand {
+        ExplorerCommand::Run(vec![
+            "ya".into(),
+            "emit-to".into(),
+            session_id.to_string(),
+            "reveal".into(),
+            path.into(),
+        ])
+    }
+}
+
+pub struct Broot;
+
+impl Explorer for Broot {
+    fn reveal(&self, path: &str, _session_id: u64) -> ExplorerCommand {
+        ExplorerCommand::Keys(vec![
+            WriteToPane::Escape,
+            WriteToPane::String(format!(":focus {}", quote_path(path))),
+            WriteToPane::Enter,
+        ])
+    }
+}
+
+pub struct Lf;
+
+impl Explorer for Lf {
+    fn reveal(&self, path: &str, _session_id: u64) -> ExplorerCommand {
+        ExplorerCommand::Keys(vec![
+            WriteToPane::Escape,
+            WriteToPane::String(format!(":select {}", quote_path(path))),
+            WriteToPane::Enter,
+        ])
+    }
+}
+
+// quote_path quotes path for the command line of broot and lf.
+fn quote_path(path: &str) -> String {
+    let path = path.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{path}\"")
+}
+
+// Explorers picks the file explorer of a session, configured the same way as
+// the editor:
+//
+//   file_explorer           yazi (default), broot or lf
+//   file_explorer.<layout>  file explorer of the sessions opened with <layout>
+pub struct Explorers {
+    default: Box<dyn Explorer>,
+    layouts: BTreeMap<String, Box<dyn Explorer>>,
+}
+
+impl Default for Explorers {
+    fn default() -> Self {
+        Explorers {
+            default: Box::new(Yazi),
+            layouts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Explorers {
+    pub fn from_config(config: &BTreeMap<String, String>) -> Result<Self> {
+        let mut explorers = Explorers::default();
+        for (key, name) in config {
+            if key == "file_explorer" {
+                explorers.default = explorer_from_name(name)?;
+            } else if let Some(layout) = key.strip_prefix("file_explorer.") {
+                explorers
+                    .layouts
+                    .insert(layout.into(), explorer_from_name(name)?);
+            }
+        }
+
+        Ok(explorers)
+    }
+
+    pub fn get(&self, layout: Option<&str>) -> &dyn Explorer {
+        layout
+            .and_then(|layout| self.layouts.get(layout))
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
+
+pub fn explorer_from_name(name: &str) -> Result<Box<dyn Explorer>> {
+    match name {
+        "yazi" => Ok(Box::new(Yazi)),
+        "broot" => Ok(Box::new(Broot)),
+        "lf" => Ok(Box::new(Lf)),
+        _ => Err(format!(
+            "invalid file_explorer {name}, valid: yazi, broot, lf"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yazi_reveal() {
+        assert_eq!(
+            Yazi.reveal("/tmp/a b.rs", 1234),
+            ExplorerCommand::Run(vec![
+                "ya".into(),
+                "emit-to".into(),
+                "1234".into(),
+                "reveal".into(),
+                "/tmp/a b.rs".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_broot_reveal() {
+        assert_eq!(
+            Broot.reveal("/tmp/a b.rs", 1),
+            ExplorerCommand::Keys(vec![
+                WriteToPane::Escape,
+                WriteToPane::String(r#":focus "/tmp/a b.rs""#.into()),
+                WriteToPane::Enter,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lf_reveal() {
+        assert_eq!(
+            Lf.reveal(r#"/tmp/"a".rs"#, 1),
+            ExplorerCommand::Keys(vec![
+                WriteToPane::Escape,
+                WriteToPane::String(r#":select "/tmp/\"a\".rs""#.into()),
+                WriteToPane::Enter,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_explorers_from_config() {
+        let config = BTreeMap::from([
+            ("file_explorer".to_string(), "lf".to_string()),
+            ("file_explorer.broot".to_string(), "broot".to_string()),
+        ]);
+        let explorers = Explorers::from_config(&config).unwrap();
+        let reveal = |layout| explorers.get(layout).reveal("/tmp", 1);
+
+        assert_eq!(reveal(None), Lf.reveal("/tmp", 1));
+        assert_eq!(reveal(Some("default")), Lf.reveal("/tmp", 1));
+        assert_eq!(reveal(Some("broot")), Broot.reveal("/tmp", 1));
+        assert_eq!(
+            Explorers::default().get(None).reveal("/tmp", 1),
+            Yazi.reveal("/tmp", 1)
+        );
+    }
+
+    #[test]
+    fn test_explorers_invalid_explorer() {
+        let config = BTreeMap::from([("file_explorer".to_string(), "ranger".to_string())]);
+
+        assert_eq!(
+            Explorers::from_config(&config).err().unwrap(),
+            "invalid file_explorer ranger, valid: yazi, broot, lf".to_string()
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// fuzzy_score matches query as a case insensitive subsequence of candidate.
+// Matches right after a path or word separator and consecutive matches score
+// higher, so `hd` prefers `~/src/hide` over `~/src/shadow`. Returns None when
+// the query doesn't match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut prev: Option<char> = None;
+    let mut prev_matched = false;
+
+    for ch in candidate.chars() {
+        let Some(&wanted) = query.peek() else {
+            break;
+        };
+
+        let matched = ch.to_lowercase().eq(std::iter::once(wanted));
+        if matched {
+            score += 1;
+            if prev_matched {
+                score += 8;
+            }
+            if prev.is_none_or(|p| matches!(p, '/' | '-' | '_' | '.' | ' ')) {
+                score += 4;
+            }
+            query.next();
+        }
+
+        prev_matched = matched;
+        prev = Some(ch);
+    }
+
+    if query.peek().is_some() {
+        return None;
+    }
+
+    // among equally good matches prefer the shorter candidate
+    Some(score * 1000 - candidate.chars().count() as i64)
+}
+
+// fuzzy_filter returns the indices of the candidates matching query, best first.
+// An empty query keeps the candidates in their order.
+pub fn fuzzy_filter<S: AsRef<str>>(query: &str, candidates: &[S]) -> Vec<usize> {
+    if query.is_empty() {
+        return (0..candidates.len()).collect();
+    }
+
+    let mut matches: Vec<(i64, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, c)| fuzzy_score(query, c.as_ref()).map(|score| (score, idx)))
+        .collect();
+
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    matches.into_iter().map(|(_, idx)| idx).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_subsequence() {
+        assert!(fuzzy_score("hde", "/src/hide").is_some());
+        assert!(fuzzy_score("HIDE", "/src/hide").is_some());
+        assert!(fuzzy_score("", "/src/hide").is_some());
+        assert!(fuzzy_score("hidden", "/src/hide").is_none());
+        assert!(fuzzy_score("edih", "/src/hide").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_score_unicode() {
+        assert!(fuzzy_score("ünï", "/src/ÜNÏCODE").is_some());
+        assert!(fuzzy_score("ü", "/src/u").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_filter_prefers_word_starts() {
+        let candidates = ["/src/shadow", "/src/hide", "/src/zellij-hide"];
+
+        assert_eq!(fuzzy_filter("hd", &candidates), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_fuzzy_filter_prefers_consecutive() {
+        let candidates = ["/src/z-e-l-l-i-j", "/src/zellij"];
+
+        assert_eq!(fuzzy_filter("zell", &candidates), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_fuzzy_filter_prefers_shorter() {
+        let candidates = ["/src/hide-old", "/src/hide"];
+
+        assert_eq!(fuzzy_filter("hide", &candidates), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_fuzzy_filter_empty_query_keeps_order() {
+        let candidates = ["/src/b", "/src/a"];
+
+        assert_eq!(fuzzy_filter("", &candidates), vec![0, 1]);
+    }
+}
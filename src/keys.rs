@@ -0,0 +1,315 @@
+use crate::{Result, WriteToPane};
+
+const ESC: u8 = 0x1b;
+
+// Repeating a key more than this many times is almost certainly a typo, and
+// would flood the pane.
+const MAX_REPEAT: usize = 1000;
+
+// The keys with a name and what a terminal sends for them. The first name is the
+// key's, the others are aliases.
+const NAMED_KEYS: &[(&[&str], &[u8])] = &[
+    (&["enter", "ret", "cr"], b"\r"),
+    (&["esc"], b"\x1b"),
+    (&["tab"], b"\t"),
+    (&["backspace", "bs"], b"\x7f"),
+    (&["space"], b" "),
+    (&["lt"], b"<"),
+    (&["gt"], b">"),
+    (&["del"], b"\x1b[3~"),
+    (&["ins"], b"\x1b[2~"),
+    (&["home"], b"\x1b[H"),
+    (&["end"], b"\x1b[F"),
+    (&["pageup"], b"\x1b[5~"),
+    (&["pagedown"], b"\x1b[6~"),
+    (&["up"], b"\x1b[A"),
+    (&["down"], b"\x1b[B"),
+    (&["left"], b"\x1b[D"),
+    (&["right"], b"\x1b[C"),
+];
+
+// What F1 to F12 send.
+const FUNCTION_KEYS: [&[u8]; 12] = [
+    b"\x1bOP",
+    b"\x1bOQ",
+    b"\x1bOR",
+    b"\x1bOS",
+    b"\x1b[15~",
+    b"\x1b[17~",
+    b"\x1b[18~",
+    b"\x1b[19~",
+    b"\x1b[20~",
+    b"\x1b[21~",
+    b"\x1b[23~",
+    b"\x1b[24~",
+];
+
+// valid_keys lists the keys parse_key accepts, for its errors.
+fn valid_keys() -> String {
+    let mut keys: Vec<String> = NAMED_KEYS
+        .iter()
+        .map(|(names, _)| format!("<{}>", names[0]))
+        .collect();
+    keys.push(format!("<F1>..<F{}>", FUNCTION_KEYS.len()));
+    for (names, _) in NAMED_KEYS.iter().filter(|(names, _)| names.len() > 1) {
+        let aliases: Vec<String> = names[1..].iter().map(|name| format!("<{name}>")).collect();
+        keys.push(format!("{} for <{}>", aliases.join(" and "), names[0]));
+    }
+    keys.push("modifiers <C-x>, <A-x>, <S-tab>, raw <bytes:hex> and repeats <key*N>".into());
+    keys.join(", ")
+}
+
+// parse_key encodes a `<symbol>` of write_to_pane data, without the angle
+// brackets, to what a terminal sends for that key. Keys use the helix/vim
+// notation:
+//
+//   <enter> <esc> <tab> <backspace> <space> <lt> <gt> <del> <ins> <home>
+//   <end> <pageup> <pagedown> <up> <down> <left> <right> <F1>..<F12>
+//   <ret> and <cr> for <enter>, <bs> for <backspace>
+//   <C-x>    ctrl with a character
+//   <A-x>    alt with any key, e.g. <A-enter> or <A-C-x>
+//   <S-tab>  shift with tab, or a character for its upper case
+//   <bytes:hex>  raw bytes, e.g. <bytes:1b5b41> for the up arrow
+//   <key*N>  the key N times, e.g. <down*3>
+//
+// Key names ignore case.
+pub fn parse_key(symbol: &str) -> Result<Vec<WriteToPane>> {
+    let invalid = || format!("invalid <symbol>: {symbol}, valid: {}", valid_keys());
+
+    let (key, count) = match symbol.rsplit_once('*') {
+        Some((key, count))
+            if !key.is_empty()
+                && !count.is_empty()
+                && count.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            let count: usize = count.parse().map_err(|_| invalid())?;
+            if count == 0 || count > MAX_REPEAT {
+                return Err(format!(
+                    "invalid repeat count in <{symbol}>, expected 1 to {MAX_REPEAT}"
+                ));
+            }
+            (key, count)
+        }
+        _ => (symbol, 1),
+    };
+
+    if let Some(hex) = key.strip_prefix("bytes:") {
+        let bytes = decode_hex(hex)
+            .ok_or_else(|| format!("invalid <{symbol}>, expected bytes as pairs of hex digits"))?;
+        return Ok(vec![WriteToPane::Bytes(bytes); count]);
+    }
+
+    let mut rest = key;
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+    while rest.chars().count() > 2 && rest.chars().nth(1) == Some('-') {
+        match rest.chars().next() {
+            Some('C' | 'c') => ctrl = true,
+            Some('A' | 'a') => alt = true,
+            Some('S' | 's') => shift = true,
+            _ => return Err(invalid()),
+        }
+        rest = &rest[2..];
+    }
+
+    let mut chars = rest.chars();
+    let encoded = match (chars.next(), chars.next()) {
+        (Some(ch), None) => encode_char(ch, ctrl, shift),
+        _ => match (rest.to_ascii_lowercase().as_str(), ctrl, shift) {
+            ("space", true, false) => Some(vec![0]),
+            ("tab", false, true) => Some(vec![ESC, b'[', b'Z']),
+            (name, false, false) => encode_name(name),
+            _ => None,
+        },
+    }
+    .ok_or_else(invalid)?;
+
+    let write = match (alt, encoded.as_slice()) {
+        (false, [13]) => WriteToPane::Enter,
+        (false, [ESC]) => WriteToPane::Escape,
+        (false, _) => WriteToPane::Bytes(encoded),
+        (true, _) => WriteToPane::Bytes([vec![ESC], encoded].concat()),
+    };
+
+    Ok(vec![write; count])
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) || !hex.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok())
+        .collect()
+}
+
+fn encode_char(ch: char, ctrl: bool, shift: bool) -> Option<Vec<u8>> {
+    let ch = if shift { ch.to_uppercase().next()? } else { ch };
+    if !ctrl {
+        return Some(ch.to_string().into_bytes());
+    }
+
+    match ch.to_ascii_lowercase() {
+        ch @ ('a'..='z' | '@' | '[' | '\\' | ']' | '^' | '_') => Some(vec![ch as u8 & 0x1f]),
+        ' ' => Some(vec![0]),
+        '?' => Some(vec![0x7f]),
+        _ => None,
+    }
+}
+
+fn encode_name(name: &str) -> Option<Vec<u8>> {
+    let encoded = NAMED_KEYS
+        .iter()
+        .find(|(names, _)| names.contains(&name))
+        .map(|(_, encoded)| *encoded)
+        .or_else(|| {
+            FUNCTION_KEYS
+                .iter()
+                .enumerate()
+                .find(|(idx, _)| name == format!("f{}", idx + 1))
+                .map(|(_, encoded)| *encoded)
+        })?;
+
+    Some(encoded.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(symbol: &str) -> Vec<u8> {
+        match parse_key(symbol).unwrap().as_slice() {
+            [WriteToPane::Bytes(bytes)] => bytes.clone(),
+            [WriteToPane::Enter] => vec![13],
+            [WriteToPane::Escape] => vec![ESC],
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_key_named() {
+        assert_eq!(parse_key("enter").unwrap(), vec![WriteToPane::Enter]);
+        assert_eq!(parse_key("esc").unwrap(), vec![WriteToPane::Escape]);
+        assert_eq!(bytes("tab"), vec![9]);
+        assert_eq!(bytes("backspace"), vec![0x7f]);
+        assert_eq!(bytes("space"), b" ");
+        assert_eq!(bytes("lt"), b"<");
+        assert_eq!(bytes("del"), b"\x1b[3~");
+        assert_eq!(bytes("pagedown"), b"\x1b[6~");
+        assert_eq!(bytes("Up"), b"\x1b[A");
+        assert_eq!(bytes("down"), b"\x1b[B");
+        assert_eq!(bytes("right"), b"\x1b[C");
+        assert_eq!(bytes("left"), b"\x1b[D");
+    }
+
+    #[test]
+    fn test_parse_key_aliases() {
+        assert_eq!(parse_key("ret").unwrap(), vec![WriteToPane::Enter]);
+        assert_eq!(parse_key("CR").unwrap(), vec![WriteToPane::Enter]);
+        assert_eq!(bytes("bs"), bytes("backspace"));
+        assert_eq!(bytes("gt"), b">");
+        assert_eq!(bytes("ins"), b"\x1b[2~");
+    }
+
+    #[test]
+    fn test_valid_keys() {
+        assert_eq!(
+            valid_keys(),
+            "<enter>, <esc>, <tab>, <backspace>, <space>, <lt>, <gt>, <del>, <ins>, <home>, \
+<end>, <pageup>, <pagedown>, <up>, <down>, <left>, <right>, <F1>..<F12>, \
+<ret> and <cr> for <enter>, <bs> for <backspace>, \
+modifiers <C-x>, <A-x>, <S-tab>, raw <bytes:hex> and repeats <key*N>"
+        );
+        // every key listed is accepted
+        for (names, _) in NAMED_KEYS {
+            for name in *names {
+                assert!(parse_key(name).is_ok(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_key_function_keys() {
+        assert_eq!(bytes("F1"), b"\x1bOP");
+        assert_eq!(bytes("f4"), b"\x1bOS");
+        assert_eq!(bytes("F5"), b"\x1b[15~");
+        assert_eq!(bytes("F10"), b"\x1b[21~");
+        assert_eq!(bytes("F12"), b"\x1b[24~");
+        assert!(parse_key("F13").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_modifiers() {
+        assert_eq!(bytes("C-c"), vec![3]);
+        assert_eq!(bytes("C-A"), vec![1]);
+        assert_eq!(bytes("C-["), vec![ESC]);
+        assert_eq!(bytes("C-space"), vec![0]);
+        assert_eq!(bytes("A-x"), b"\x1bx");
+        assert_eq!(bytes("A-enter"), b"\x1b\r");
+        assert_eq!(bytes("A-up"), b"\x1b\x1b[A");
+        assert_eq!(bytes("A-C-x"), vec![ESC, 24]);
+        assert_eq!(bytes("C-A-x"), vec![ESC, 24]);
+        assert_eq!(bytes("A--"), b"\x1b-");
+        assert_eq!(bytes("S-tab"), b"\x1b[Z");
+        assert_eq!(bytes("S-x"), b"X");
+        assert_eq!(bytes("A-ü"), "\x1bü".as_bytes());
+    }
+
+    #[test]
+    fn test_parse_key_repeat() {
+        assert_eq!(
+            parse_key("down*3").unwrap(),
+            vec![WriteToPane::Bytes(b"\x1b[B".to_vec()); 3]
+        );
+        assert_eq!(
+            parse_key("enter*2").unwrap(),
+            vec![WriteToPane::Enter, WriteToPane::Enter]
+        );
+        assert_eq!(bytes("*"), b"*");
+        assert_eq!(bytes("A-*"), b"\x1b*");
+    }
+
+    #[test]
+    fn test_parse_key_bytes() {
+        assert_eq!(bytes("bytes:1b5b41"), b"\x1b[A");
+        assert_eq!(bytes("bytes:00FF"), vec![0, 0xff]);
+        assert_eq!(bytes("bytes:0d"), vec![13]);
+        assert_eq!(
+            parse_key("bytes:07*2").unwrap(),
+            vec![WriteToPane::Bytes(vec![7]); 2]
+        );
+    }
+
+    #[test]
+    fn test_parse_key_invalid_bytes() {
+        for symbol in ["bytes:", "bytes:1", "bytes:zz", "bytes:+1", "bytes:ü1"] {
+            assert_eq!(
+                parse_key(symbol).err().unwrap(),
+                format!("invalid <{symbol}>, expected bytes as pairs of hex digits"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_key_invalid_repeat() {
+        assert_eq!(
+            parse_key("down*0").err().unwrap(),
+            "invalid repeat count in <down*0>, expected 1 to 1000".to_string()
+        );
+        assert!(parse_key("down*1001").is_err());
+        assert!(parse_key("down*99999999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_invalid() {
+        for symbol in ["", "invalid", "C-up", "C-é", "S-up", "X-x", "C-"] {
+            assert_eq!(
+                parse_key(symbol).err().unwrap(),
+                format!("invalid <symbol>: {symbol}, valid: {}", valid_keys()),
+                "{symbol}"
+            );
+        }
+    }
+}
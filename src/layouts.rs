@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::Result;
+
+pub const DEFAULT_LAYOUT: &str = "default";
+
+// Layouts keeps track of the layouts new instances can be created with. They are
+// configured in the plugin configuration:
+//
+//   layout_dir      every <name>.kdl file in the directory is a layout named <name>
+//   layout.<name>   path of the layout named <name>, takes precedence over layout_dir
+//   default_layout  layout used when new_instance doesn't ask for one
+//
+// Files are read when a tab is created so editing a layout doesn't need a plugin
+// reload. The layout compiled into the plugin is available as `default` unless
+// it's overridden.
+#[derive(Debug, Default)]
+pub struct Layouts {
+    default: String,
+    builtin: String,
+    files: BTreeMap<String, PathBuf>,
+}
+
+impl Layouts {
+    pub fn new(builtin: &str) -> Self {
+        Layouts {
+            default: DEFAULT_LAYOUT.into(),
+            builtin: builtin.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_config(config: &BTreeMap<String, String>, builtin: &str) -> Result<Self> {
+        let mut layouts = Layouts::new(builtin);
+
+        if let Some(dir) = config.get("layout_dir") {
+            let entries = fs::read_dir(dir).map_err(|e| format!("read layout_dir {dir}: {e}"))?;
+            for entry in entries {
+                let path = entry
+                    .map_err(|e| format!("read layout_dir {dir}: {e}"))?
+                    .path();
+                if path.extension().is_none_or(|ext| ext != "kdl") {
+                    continue;
+                }
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    layouts.files.insert(name.into(), path.clone());
+                }
+            }
+        }
+
+        for (key, path) in config {
+            if let Some(name) = key.strip_prefix("layout.") {
+                layouts.files.insert(name.into(), path.into());
+            }
+        }
+
+        if let Some(default) = config.get("default_layout") {
+            if !layouts.contains(default) {
+                return Err(format!("invalid default_layout {default}"));
+            }
+            layouts.default = default.clone();
+        }
+
+        Ok(layouts)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name == DEFAULT_LAYOUT || self.files.contains_key(name)
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.files.keys().map(String::as_str).collect();
+        if !self.files.contains_key(DEFAULT_LAYOUT) {
+            names.insert(0, DEFAULT_LAYOUT);
+        }
+        names
+    }
+
+    // get returns the contents of the layout with the given name, or of the
+    // default layout when no name is given.
+    pub fn get(&self, name: Option<&str>) -> Result<String> {
+        let name = name.unwrap_or(&self.default);
+
+        if let Some(path) = self.files.get(name) {
+            return fs::read_to_string(path)
+                .map_err(|e| format!("read layout {name} from {path:?}: {e}"));
+        }
+
+        if name == DEFAULT_LAYOUT {
+            return Ok(self.builtin.clone());
+        }
+
+        Err(format!(
+            "invalid layout {name}, available: {}",
+            self.names().join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hide-layouts-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_layouts_builtin_default() {
+        let layouts = Layouts::from_config(&BTreeMap::new(), "builtin").unwrap();
+
+        assert_eq!(layouts.get(None).unwrap(), "builtin");
+        assert_eq!(layouts.get(Some("default")).unwrap(), "builtin");
+        assert_eq!(layouts.default_name(), "default");
+        assert_eq!(layouts.names(), vec!["default"]);
+    }
+
+    #[test]
+    fn test_layouts_from_layout_dir() {
+        let dir = layout_dir(
+            "dir",
+            &[("nu.kdl", "nu layout"), ("notes.txt", "not a layout")],
+        );
+        let config = BTreeMap::from([
+            ("layout_dir".to_string(), dir.to_string_lossy().to_string()),
+            ("default_layout".to_string(), "nu".to_string()),
+        ]);
+        let layouts = Layouts::from_config(&config, "builtin").unwrap();
+
+        assert_eq!(layouts.get(None).unwrap(), "nu layout");
+        assert_eq!(layouts.get(Some("default")).unwrap(), "builtin");
+        assert_eq!(layouts.default_name(), "nu");
+        assert_eq!(layouts.names(), vec!["default", "nu"]);
+    }
+
+    #[test]
+    fn test_layouts_named_path_overrides_layout_dir() {
+        let dir = layout_dir(
+            "named",
+            &[("default.kdl", "dir default"), ("other.kdl", "other")],
+        );
+        let config = BTreeMap::from([
+            ("layout_dir".to_string(), dir.to_string_lossy().to_string()),
+            (
+                "layout.default".to_string(),
+                dir.join("other.kdl").to_string_lossy().to_string(),
+            ),
+        ]);
+        let layouts = Layouts::from_config(&config, "builtin").unwrap();
+
+        assert_eq!(layouts.get(None).unwrap(), "other");
+        assert_eq!(layouts.names(), vec!["default", "other"]);
+    }
+
+    #[test]
+    fn test_layouts_invalid_layout() {
+        let layouts = Layouts::new("builtin");
+
+        assert_eq!(
+            layouts.get(Some("fish")).err().unwrap(),
+            "invalid layout fish, available: default".to_string()
+        );
+    }
+
+    #[test]
+    fn test_layouts_invalid_default_layout() {
+        let config = BTreeMap::from([("default_layout".to_string(), "fish".to_string())]);
+        let layouts = Layouts::from_config(&config, "builtin");
+
+        assert_eq!(
+            layouts.err().unwrap(),
+            "invalid default_layout fish".to_string()
+        );
+    }
+
+    #[test]
+    fn test_layouts_missing_layout_dir() {
+        let config =
+            BTreeMap::from([("layout_dir".to_string(), "/does/not/exist/hide".to_string())]);
+        let layouts = Layouts::from_config(&config, "builtin");
+
+        assert!(
+            layouts
+                .err()
+                .unwrap()
+                .starts_with("read layout_dir /does/not/exist/hide")
+        );
+    }
+}
@@ -1,25 +1,34 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 use zellij_tile::prelude::PaneInfo;
 
-pub type Result<T> = std::result::Result<T, String>;
-
-#[derive(Debug)]
-pub struct NewInstanceMessage {
-    pub name: String,
-    pub path: String,
-}
-
-#[derive(Debug)]
-pub struct EditFileMessage {
-    pub path: String,
-}
+mod classify;
+mod dashboard;
+mod editor;
+mod explorer;
+mod fuzzy;
+mod keys;
+mod layouts;
+mod picker;
+mod protocol;
+mod sessions;
+mod template;
+
+pub use classify::*;
+pub use dashboard::*;
+pub use editor::*;
+pub use explorer::*;
+pub use fuzzy::*;
+pub use keys::*;
+pub use layouts::*;
+pub use picker::*;
+pub use protocol::*;
+pub use sessions::*;
+pub use template::*;
 
-#[derive(Debug)]
-pub struct FocusPaneMessage {
-    pub typ: String,
-}
+pub type Result<T> = std::result::Result<T, String>;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WriteToPane {
     Bytes(Vec<u8>),
     String(String),
@@ -27,7 +36,7 @@ pub enum WriteToPane {
     Escape,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WritesToPane(pub Vec<WriteToPane>);
 
 impl TryFrom<&str> for WritesToPane {
@@ -35,33 +44,26 @@ impl TryFrom<&str> for WritesToPane {
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
         let mut v = vec![];
-        let mut chars = value.chars().enumerate();
+        // indices are byte offsets, symbols are sliced out of value
+        let mut chars = value.char_indices().peekable();
         let mut str = String::new();
 
         while let Some((idx, ch)) = chars.next() {
             match ch {
+                // `<<` is a literal `<`
+                '<' if chars.next_if(|(_, next)| *next == '<').is_some() => str.push('<'),
                 '<' => {
                     if !str.is_empty() {
                         v.push(WriteToPane::String(str.clone()));
                         str.truncate(0);
                     }
 
-                    let Some(end) = chars.position(|(_, ch)| ch == '>') else {
+                    let Some((end, _)) = chars.find(|(_, ch)| *ch == '>') else {
                         return Err("< not terminated properly with a >".into());
                     };
 
-                    let symbol = &value[idx + 1..idx + end + 1];
-                    let w = match symbol {
-                        "enter" => WriteToPane::Enter,
-                        "esc" => WriteToPane::Escape,
-                        _ => {
-                            return Err(format!(
-                                "invalid <symbol>: {symbol}, valid: <enter>, <esc>"
-                            ));
-                        }
-                    };
-
-                    v.push(w);
+                    let symbol = &value[idx + 1..end];
+                    v.extend(parse_key(symbol)?);
                 }
                 _ => {
                     str.push(ch);
@@ -77,25 +79,6 @@ impl TryFrom<&str> for WritesToPane {
     }
 }
 
-#[derive(Debug)]
-pub struct WriteToPaneMessage {
-    pub typ: PaneType,
-    pub data: WritesToPane,
-}
-
-#[derive(Debug)]
-pub enum V0Message {
-    NewInstance(NewInstanceMessage),
-    EditFile(EditFileMessage),
-    FocusPane(FocusPaneMessage),
-    WriteToPane(WriteToPaneMessage),
-}
-
-#[derive(Debug)]
-pub enum Message {
-    V0(V0Message),
-}
-
 #[derive(Debug, Default, Clone)]
 pub struct InstancePane {
     pub info: PaneInfo,
@@ -103,8 +86,10 @@ pub struct InstancePane {
     pub typ: PaneType,
 }
 
-#[derive(Debug, Eq, Clone)]
+#[derive(Debug, Default, Eq, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum PaneType {
+    #[default]
     Unknown,
     Editor,
     FileExplorer,
@@ -121,9 +106,15 @@ impl PartialEq for PaneType {
     }
 }
 
-impl Default for PaneType {
-    fn default() -> Self {
-        PaneType::Unknown
+impl PaneType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PaneType::Unknown => "unknown",
+            PaneType::Editor => "editor",
+            PaneType::FileExplorer => "file_explorer",
+            PaneType::Terminal => "terminal",
+            PaneType::Custom(name) => name.as_str(),
+        }
     }
 }
 
@@ -150,91 +141,182 @@ impl From<&str> for PaneType {
     }
 }
 
-pub fn parse_pipe_message(payload: &str) -> Result<Message> {
-    // versioning will become more enforced with v1
-    let version = payload.chars().next().ok_or("invalid payload len 0")?;
-    if !version.is_numeric() {
-        return Err("first character must be a protocol version".into());
+impl From<String> for PaneType {
+    fn from(value: String) -> Self {
+        value.as_str().into()
     }
+}
 
-    match version {
-        '0' => parse_v0_message(&payload[1..]),
-        _ => return Err("invalid protocol version {version}, only '0' is supported".into()),
+impl From<PaneType> for String {
+    fn from(value: PaneType) -> Self {
+        value.as_str().into()
     }
 }
 
-macro_rules! extract_message_key {
-    ($kvs:expr, $key:expr) => {
-        (*$kvs.get($key).ok_or(format!("{} is required", $key))?).into()
-    };
+pub fn extract_session_id_from_cmd(terminal_command: &str) -> Option<u64> {
+    extract_env_from_cmd(terminal_command, "SESSION_ID").find_map(|v| v.parse().ok())
 }
 
-fn parse_v0_message(payload: &str) -> Result<Message> {
-    let mut idx_start = 0 as usize;
-    let mut parts = vec![];
-    for (mut idx, ch) in payload.chars().skip(1).enumerate() {
-        idx += 1;
-        if ch == ';' {
-            parts.push(&payload[idx_start..idx]);
-            idx_start = idx + 1;
-        }
-    }
-
-    let command = parts
-        .first()
-        .ok_or("command is required after protocol version")?;
-
-    let mut kvs = HashMap::new();
-    for part in parts.iter().skip(1) {
-        let idx = part.find("=").ok_or("no kv pair in command args")?;
-
-        let k = &part[..idx];
-        let v = &part[idx + 1..];
+// extract_role_from_cmd returns the type a layout gives a pane by setting
+// HIDE_ROLE in its command, e.g. `HIDE_ROLE=editor hide-cli run hx`.
+pub fn extract_role_from_cmd(terminal_command: &str) -> Option<PaneType> {
+    extract_env_from_cmd(terminal_command, "HIDE_ROLE")
+        .find(|v| {
+            !v.is_empty()
+                && v.chars()
+                    .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '-'))
+        })
+        .map(PaneType::from)
+}
 
-        kvs.insert(k, v);
-    }
+// extract_env_from_cmd finds the values assigned to the env variable `name` anywhere
+// in a pane's command, regardless of the shell used to set it, e.g.
+//
+//   NAME=value cmd, env NAME=value cmd, export NAME="value";   (sh, bash, zsh)
+//   set -x NAME value;                                         (fish)
+//   $env.NAME = value; with-env { NAME: value } { cmd }        (nu)
+//
+// Values are unquoted the way shells do it, e.g. `NAME='/a b'\''c'` is `/a b'c`.
+// Mentions of `name` that aren't assignments yield whatever word follows them, so
+// callers are expected to validate the values.
+pub fn extract_env_from_cmd<'a>(
+    terminal_command: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let is_ident = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let is_separator = |ch: char| ch.is_whitespace() || matches!(ch, '=' | ':');
+
+    terminal_command
+        .match_indices(name)
+        .filter_map(move |(idx, _)| {
+            let before = terminal_command[..idx].chars().next_back();
+            let mut rest = &terminal_command[idx + name.len()..];
+            if before.is_some_and(is_ident) || rest.starts_with(is_ident) {
+                return None;
+            }
 
-    // TODO: move this to the message to reduce amount of places we need to change
-    let message = match *command {
-        "new_instance" => V0Message::NewInstance(NewInstanceMessage {
-            name: extract_message_key!(kvs, "name"),
-            path: extract_message_key!(kvs, "path"),
-        }),
-        "edit_file" => V0Message::EditFile(EditFileMessage {
-            path: extract_message_key!(kvs, "path"),
-        }),
-        "focus_pane" => V0Message::FocusPane(FocusPaneMessage {
-            typ: extract_message_key!(kvs, "type"),
-        }),
-        "write_to_pane" => V0Message::WriteToPane(WriteToPaneMessage {
-            typ: extract_message_key!(kvs, "type"),
-            data: {
-                let data_str: String = extract_message_key!(kvs, "data");
-                data_str.as_str().try_into()?
-            },
-        }),
-        _ => return Err(format!("invalid protocol message {command}")),
-    };
+            // nu records may quote the key, e.g. {"NAME": value}
+            if let Some(quote) = before.filter(|ch| matches!(ch, '"' | '\''))
+                && let Some(unquoted) = rest.strip_prefix(quote)
+            {
+                rest = unquoted;
+            }
 
-    Ok(Message::V0(message))
+            let value = shell_word(rest.trim_start_matches(is_separator))?;
+            (!value.is_empty()).then_some(value)
+        })
 }
 
-pub fn extract_session_id_from_cmd(terminal_command: &str) -> Option<u128> {
-    const SESSION_ID_MARKER: &str = "SESSION_ID=";
-
-    let session_id_idx = terminal_command.find(SESSION_ID_MARKER)? + SESSION_ID_MARKER.len();
-    let session_id_end_idx = terminal_command[session_id_idx..].find(' ')?;
-    let session_id = terminal_command[session_id_idx..session_id_idx + session_id_end_idx]
-        .parse::<u128>()
-        .ok()?;
+// shell_word reads the word rest starts with, joining its quoted and escaped
+// parts. The word ends at whitespace or punctuation ending a value in one of the
+// shells above, None when a quote isn't closed.
+fn shell_word(rest: &str) -> Option<String> {
+    let is_value_end = |ch: char| ch.is_whitespace() || matches!(ch, ';' | ',' | '}' | ')');
+
+    let mut word = String::new();
+    let mut chars = rest.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    ch => word.push(ch),
+                }
+            },
+            '"' => loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        ch @ ('"' | '\\') => word.push(ch),
+                        ch => {
+                            word.push('\\');
+                            word.push(ch);
+                        }
+                    },
+                    ch => word.push(ch),
+                }
+            },
+            '\\' => word.extend(chars.next()),
+            ch if is_value_end(ch) => break,
+            ch => word.push(ch),
+        }
+    }
 
-    return Some(session_id);
+    Some(word)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // random_inputs returns count pseudo random strings made of ascii, the
+    // separators and symbols the parsers look for, multi-byte characters and
+    // arbitrary code points. The same seed gives the same strings, a failure can
+    // be reproduced.
+    pub(crate) fn random_inputs(seed: u64, count: usize) -> Vec<String> {
+        const TOKENS: &[&str] = &[
+            "a",
+            "Z",
+            "0",
+            "9",
+            " ",
+            "<",
+            ">",
+            "<<",
+            "<enter>",
+            "<C-",
+            "<down*",
+            ";",
+            "=",
+            "*",
+            "-",
+            ":",
+            "{",
+            "}",
+            "\"",
+            "'",
+            "\\",
+            "/",
+            "SESSION_ID",
+            "é",
+            "ß",
+            "€",
+            "中",
+            "🦀",
+            "\u{301}",
+            "\0",
+            "\n",
+        ];
+
+        // xorshift64, the seed must not be 0
+        let mut state = seed.max(1);
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        (0..count)
+            .map(|_| {
+                let len = next() % 16;
+                let mut input = String::new();
+                for _ in 0..len {
+                    if next() % 4 > 0 {
+                        input.push_str(TOKENS[next() % TOKENS.len()]);
+                        continue;
+                    }
+
+                    // any scalar value, surrogates aren't chars and are skipped
+                    if let Some(ch) = char::from_u32((next() % 0x110000) as u32) {
+                        input.push(ch);
+                    }
+                }
+                input
+            })
+            .collect()
+    }
+
     #[test]
     fn test_writes_to_pane_empty() {
         let input = "";
@@ -265,101 +347,104 @@ mod tests {
         let input = "<invalid>";
         let result = WritesToPane::try_from(input);
         assert!(result.is_err());
-        assert_eq!(
-            result.err().unwrap(),
-            "invalid <symbol>: invalid, valid: <enter>, <esc>".to_string()
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .starts_with("invalid <symbol>: invalid, valid: <enter>, <esc>, <tab>")
         );
     }
 
     #[test]
-    fn test_writes_to_pane_unclosed_symbol() {
-        let input = "data=<esc";
-        let result = WritesToPane::try_from(input);
-        assert!(result.is_err());
+    fn test_writes_to_pane_unicode() {
+        let input = "échö 🦀<enter>中文<C-c>ü";
+        let result = WritesToPane::try_from(input).unwrap();
         assert_eq!(
-            result.err().unwrap(),
-            "< not terminated properly with a >".to_string()
+            result.0,
+            vec![
+                WriteToPane::String("échö 🦀".into()),
+                WriteToPane::Enter,
+                WriteToPane::String("中文".into()),
+                WriteToPane::Bytes(vec![3]),
+                WriteToPane::String("ü".into()),
+            ]
         );
     }
 
     #[test]
-    fn test_parse_pipe_message_new_instance_with_path() {
-        let payload = "0new_instance;name=test_instance;path=/tmp;";
-        let message = parse_pipe_message(payload).unwrap();
-
-        match message {
-            Message::V0(V0Message::NewInstance(instance)) => {
-                assert_eq!(instance.name, "test_instance");
-                assert_eq!(instance.path, "/tmp");
+    fn test_writes_to_pane_unicode_symbol() {
+        let result = WritesToPane::try_from("é<ünï>");
+        assert!(result.err().unwrap().starts_with("invalid <symbol>: ünï,"));
+    }
+
+    #[test]
+    fn test_writes_to_pane_fuzz() {
+        for input in random_inputs(0x5eed, 5000) {
+            let result = WritesToPane::try_from(input.as_str());
+
+            // without symbols the data is typed as is
+            if !input.contains('<') {
+                let expected = if input.is_empty() {
+                    vec![]
+                } else {
+                    vec![WriteToPane::String(input.clone())]
+                };
+                assert_eq!(result.unwrap().0, expected, "{input:?}");
             }
-            _ => unreachable!(),
         }
     }
 
     #[test]
-    fn test_parse_pipe_message_edit_file() {
-        let payload = "0edit_file;path=/tmp/foo.txt;";
-        let message = parse_pipe_message(payload).unwrap();
-
-        match message {
-            Message::V0(V0Message::EditFile(instance)) => {
-                assert_eq!(instance.path, "/tmp/foo.txt");
+    fn test_extract_env_from_cmd_fuzz() {
+        for input in random_inputs(0xc0ffee, 5000) {
+            for value in extract_env_from_cmd(&input, "SESSION_ID") {
+                assert!(value.chars().all(|ch| input.contains(ch)), "{input:?}");
             }
-            _ => unreachable!(),
+            let _ = extract_session_id_from_cmd(&input);
         }
     }
 
     #[test]
-    fn test_parse_pipe_message_invalid_version() {
-        let payload = "xnew_instance;name=test_instance;";
-        let message = parse_pipe_message(payload);
-
-        assert!(message.is_err());
+    fn test_writes_to_pane_escaped_lt() {
+        let input = "cat <<<<EOF<enter>a<<b <lt> c";
+        let result = WritesToPane::try_from(input).unwrap();
         assert_eq!(
-            message.err().unwrap(),
-            "first character must be a protocol version".to_string()
+            result.0,
+            vec![
+                WriteToPane::String("cat <<EOF".into()),
+                WriteToPane::Enter,
+                WriteToPane::String("a<b ".into()),
+                WriteToPane::Bytes(b"<".to_vec()),
+                WriteToPane::String(" c".into()),
+            ]
         );
     }
 
     #[test]
-    fn test_parse_pipe_message_invalid_message() {
-        let payload = "0invalid_message;name=test_instance;";
-        let message = parse_pipe_message(payload);
-
-        assert!(message.is_err());
+    fn test_writes_to_pane_with_keys() {
+        let input = "<C-c>ls<down*2><A-x><tab>";
+        let result = WritesToPane::try_from(input).unwrap();
         assert_eq!(
-            message.err().unwrap(),
-            "invalid protocol message invalid_message".to_string()
+            result.0,
+            vec![
+                WriteToPane::Bytes(vec![3]),
+                WriteToPane::String("ls".into()),
+                WriteToPane::Bytes(b"\x1b[B".to_vec()),
+                WriteToPane::Bytes(b"\x1b[B".to_vec()),
+                WriteToPane::Bytes(b"\x1bx".to_vec()),
+                WriteToPane::Bytes(vec![9]),
+            ]
         );
     }
 
     #[test]
-    fn test_parse_pipe_message_missing_instance_name() {
-        let payload = "0new_instance;";
-        let message = parse_pipe_message(payload);
-
-        assert!(message.is_err());
-        assert_eq!(message.err().unwrap(), "name is required".to_string());
-    }
-
-    #[test]
-    fn test_parse_pipe_message_empty_payload() {
-        let payload = "";
-        let message = parse_pipe_message(payload);
-
-        assert!(message.is_err());
-        assert_eq!(message.err().unwrap(), "invalid payload len 0".to_string());
-    }
-
-    #[test]
-    fn test_parse_pipe_message_protocol_version_1() {
-        let payload = "1new_instance;name=test_instance;path=/tmp;";
-        let message = parse_pipe_message(payload);
-
-        assert!(message.is_err());
+    fn test_writes_to_pane_unclosed_symbol() {
+        let input = "data=<esc";
+        let result = WritesToPane::try_from(input);
+        assert!(result.is_err());
         assert_eq!(
-            message.err().unwrap(),
-            "invalid protocol version {version}, only \'0\' is supported".to_string()
+            result.err().unwrap(),
+            "< not terminated properly with a >".to_string()
         );
     }
 
@@ -391,6 +476,95 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_extract_session_id_end_of_command() {
+        let terminal_command = "fish -c SESSION_ID=1234";
+        let session_id = extract_session_id_from_cmd(terminal_command).unwrap();
+        assert_eq!(session_id, 1234);
+    }
+
+    #[test]
+    fn test_extract_session_id_shells() {
+        let terminal_commands = [
+            "bash -c SESSION_ID=1234 hx .",
+            "bash -c export SESSION_ID=1234; hx .",
+            "zsh -c env SESSION_ID='1234' SESSION_CWD=/tmp hide-cli run hx .",
+            "zsh -c SESSION_CWD=/tmp SESSION_ID=\"1234\" yazi",
+            "sh -c SESSION_ID=1234;hx",
+            "fish -c set -x SESSION_ID 1234; hx .",
+            "fish -c set -gx SESSION_CWD /tmp; set -gx SESSION_ID 1234; yazi",
+            "nu -c $env.SESSION_ID = 1234; hx .",
+            "nu -c with-env { SESSION_ID: 1234 } { hx . }",
+            "nu -c with-env {SESSION_ID: \"1234\", SESSION_CWD: /tmp} { yazi }",
+            "nu -c with-env {\"SESSION_ID\": 1234} { yazi }",
+        ];
+
+        for terminal_command in terminal_commands {
+            assert_eq!(
+                extract_session_id_from_cmd(terminal_command),
+                Some(1234),
+                "{terminal_command}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_session_id_skips_similar_names() {
+        let terminal_command = "bash -c HIDE_SESSION_ID=1 SESSION_IDS=2 SESSION_ID=1234 hx";
+        let session_id = extract_session_id_from_cmd(terminal_command).unwrap();
+        assert_eq!(session_id, 1234);
+    }
+
+    #[test]
+    fn test_extract_session_id_skips_mentions() {
+        let terminal_command = "bash -c echo SESSION_ID is; SESSION_ID=1234 hx";
+        let session_id = extract_session_id_from_cmd(terminal_command).unwrap();
+        assert_eq!(session_id, 1234);
+    }
+
+    #[test]
+    fn test_extract_env_from_cmd() {
+        let terminal_command = "bash -c SESSION_CWD=\"/tmp/ünï code\" hx";
+        let values: Vec<String> = extract_env_from_cmd(terminal_command, "SESSION_CWD").collect();
+        assert_eq!(values, vec!["/tmp/ünï code"]);
+    }
+
+    #[test]
+    fn test_extract_env_from_cmd_unquotes() {
+        let values = |terminal_command| {
+            extract_env_from_cmd(terminal_command, "SESSION_CWD").collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            values(r#"fish -c SESSION_CWD='/tmp/it'\''s a "dir"' hx"#),
+            vec![r#"/tmp/it's a "dir""#]
+        );
+        assert_eq!(
+            values(r#"fish -c SESSION_CWD='/tmp/a'\\'b' hx"#),
+            vec![r"/tmp/a\b"]
+        );
+        assert_eq!(
+            values(r#"bash -c SESSION_CWD="/tmp/\"a\" \\b\c"; hx"#),
+            vec![r#"/tmp/"a" \b\c"#]
+        );
+        assert_eq!(values(r"sh -c SESSION_CWD=/tmp/a\ b hx"), vec!["/tmp/a b"]);
+        assert!(values("sh -c SESSION_CWD='/tmp/a hx").is_empty());
+    }
+
+    #[test]
+    fn test_extract_role_from_cmd() {
+        assert_eq!(
+            extract_role_from_cmd("fish -c SESSION_ID=1 HIDE_ROLE=editor hide-cli run nvim"),
+            Some(PaneType::Editor)
+        );
+        assert_eq!(
+            extract_role_from_cmd("nu -c with-env { HIDE_ROLE: lazygit } { lazygit }"),
+            Some(PaneType::Custom("lazygit".into()))
+        );
+        assert_eq!(extract_role_from_cmd("bash -c echo HIDE_ROLE; hx"), None);
+        assert_eq!(extract_role_from_cmd("fish -c SESSION_ID=1 hx"), None);
+    }
+
     #[test]
     fn test_extract_session_id_invalid_session_id() {
         let terminal_command = "fish -c SESSION_ID=abc hide-cli run hx";
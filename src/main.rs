@@ -1,22 +1,32 @@
-#![feature(let_chains)]
-
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::Path,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use lib::*;
+use hide::Message;
+use hide::*;
 
 use zellij_tile::prelude::*;
 
-mod lib;
+static LAYOUT: &str = include_str!("../layouts/default.kdl");
+
+// Context of the project scan command, tells its result apart from other commands.
+static SCAN_PROJECTS: &str = "scan_projects";
+// Context of the commands revealing files in an explorer, the cli pipe that asked
+// for it is answered once they finish.
+static REVEAL_FILE: &str = "reveal_file";
 
-static LAYOUT: &'static str = include_str!("../layouts/default.kdl");
+// Answer is the response to a message, sent right away or once the command the
+// message started finishes.
+enum Answer {
+    Now(serde_json::Value),
+    Later,
+}
 
 #[derive(Default, Debug)]
 struct FocusedSession {
-    id: u128,
-    tab: usize,
+    id: u64,
 }
 
 #[derive(Default)]
@@ -26,11 +36,22 @@ struct State {
     pipe_backlog: VecDeque<PipeMessage>,
 
     focused_tab: TabInfo,
-    instances: HashMap<u128, Vec<InstancePane>>,
+    instances: HashMap<u64, Vec<InstancePane>>,
     // TODO: should we keep this even if there's no longer a focused pane?
     // maybe keeping it as the last focused session is fine as it will allow external
     // tools to somewhat interact with it through the cli without specifying a session explicitly
     focused_session: FocusedSession,
+    session_ids: SessionIds,
+    sessions: SessionRegistry,
+    layouts: Layouts,
+    pane_rules: PaneRules,
+    editors: Editors,
+    explorers: Explorers,
+    project_roots: Vec<String>,
+    state_dir: String,
+    // Known once zellij tells the name of the session the plugin runs in.
+    state_file: Option<String>,
+    dashboard: Dashboard,
 }
 
 impl State {
@@ -40,6 +61,7 @@ impl State {
                 if let Some(active_tab) = tabs.into_iter().find(|tab| tab.active) {
                     self.focused_tab = active_tab;
                     self.set_focused_session();
+                    return true;
                 }
             }
             Event::PaneUpdate(manifest) => {
@@ -61,19 +83,88 @@ impl State {
                             continue;
                         };
 
+                        // a role announced by hide-cli run beats the pane rules
+                        let typ = match self.sessions.role(info.id) {
+                            Some(role) => role.clone(),
+                            None => self.pane_rules.classify(&info),
+                        };
                         self.instances
                             .entry(session_id)
-                            .or_insert_with(Vec::new)
+                            .or_default()
                             .push(InstancePane {
-                                typ: PaneType::from(info.title.as_str()),
+                                typ,
                                 info,
                                 tab_index,
                             });
                     }
                 }
 
+                if self.sessions.reconcile(&self.instances, now_millis()) {
+                    self.save_sessions();
+                }
                 self.set_focused_session();
                 dbg!(&self.focused_session);
+                return true;
+            }
+            Event::ModeUpdate(mode_info) => {
+                if let Some(session_name) = &mode_info.session_name {
+                    self.set_session_name(session_name);
+                }
+            }
+            Event::Key(key) => {
+                let rows = Dashboard::rows(&self.instances, self.focused_session.id);
+                match self.dashboard.handle_key(&key, &rows) {
+                    Some(DashboardAction::Render) => return true,
+                    Some(DashboardAction::FocusPane(pane_id)) => {
+                        focus_pane_with_id(PaneId::Terminal(pane_id), true)
+                    }
+                    Some(DashboardAction::ScanProjects) => {
+                        self.scan_projects();
+                        return true;
+                    }
+                    Some(DashboardAction::OpenProject(path)) => {
+                        if let Err(e) = self.open_project(&path) {
+                            eprintln!("open project error: {e}");
+                        }
+                        return true;
+                    }
+                    Some(DashboardAction::Close) => hide_self(),
+                    None => {}
+                }
+            }
+            Event::RunCommandResult(_, stdout, _, context)
+                if context.get("source").map(String::as_str) == Some(SCAN_PROJECTS) =>
+            {
+                self.dashboard.set_projects(parse_scan_output(&stdout));
+                return true;
+            }
+            Event::RunCommandResult(exit_code, _, stderr, context)
+                if context.get("source").map(String::as_str) == Some(REVEAL_FILE) =>
+            {
+                let command = context.get("command").map_or("", String::as_str);
+                let stderr = String::from_utf8_lossy(&stderr);
+                let result = match exit_code {
+                    Some(0) => Ok(serde_json::Value::Null),
+                    Some(code) => Err(format!(
+                        "{command} failed with exit code {code}: {}",
+                        stderr.trim()
+                    )),
+                    None => Err(format!("{command} failed: {}", stderr.trim())),
+                };
+                match context.get("pipe_id") {
+                    Some(pipe_id) => answer_cli_pipe(pipe_id, result),
+                    None => {
+                        if let Err(e) = result {
+                            eprintln!("reveal_file error: {e}");
+                        }
+                    }
+                }
+            }
+            Event::RunCommandResult(exit_code, _, stderr, context) if exit_code != Some(0) => {
+                eprintln!(
+                    "run command {context:?} failed with {exit_code:?}: {}",
+                    String::from_utf8_lossy(&stderr)
+                );
             }
             _ => {}
         }
@@ -86,15 +177,11 @@ impl State {
     // although generally the order is tab event followed by pane event.
     fn set_focused_session(&mut self) {
         for (session_id, panes) in &self.instances {
-            if let Some(pane) = panes
+            if panes
                 .iter()
-                .find(|pane| pane.tab_index == self.focused_tab.position && pane.info.is_focused)
-                .map(Clone::clone)
+                .any(|pane| pane.tab_index == self.focused_tab.position && pane.info.is_focused)
             {
-                self.focused_session = FocusedSession {
-                    id: *session_id,
-                    tab: pane.tab_index,
-                };
+                self.focused_session = FocusedSession { id: *session_id };
                 break;
             }
         }
@@ -102,89 +189,396 @@ impl State {
 
     fn handle_pipe_message(&mut self, msg: PipeMessage) -> bool {
         dbg!("Handle pipe message: ", &msg);
-        // let pipe_id = match msg.source {
-        //     PipeSource::Cli(pipe_id) => pipe_id,
-        //     _ => return false,
-        // };
-
-        let payload = match msg.payload {
-            Some(payload) => payload,
-            _ => return false,
-        };
 
-        let message = match lib::parse_pipe_message(&payload) {
-            Ok(message) => message,
-            Err(err) => {
-                dbg!("handle_pipe_message err:", err);
-                return false;
+        // Only the cli waits for an answer, keybindings and plugins have nowhere to show it.
+        let cli_pipe = match &msg.source {
+            PipeSource::Cli(pipe_id) => Some(pipe_id.as_str()),
+            _ => None,
+        };
+        let result = msg
+            .payload
+            .as_deref()
+            .ok_or_else(|| "payload is required".to_string())
+            .and_then(parse_pipe_message)
+            .and_then(|message| self.handle_message(message, cli_pipe));
+
+        let result = match result {
+            Ok(Answer::Later) => return false,
+            Ok(Answer::Now(value)) => Ok(value),
+            Err(e) => {
+                eprintln!("handle_pipe_message error: {e}");
+                Err(e)
             }
         };
 
+        if let Some(pipe_id) = cli_pipe {
+            answer_cli_pipe(pipe_id, result);
+        }
+
+        false
+    }
+
+    // handle_message acts on a message, cli_pipe is the cli pipe it came from.
+    fn handle_message(&mut self, message: Message, cli_pipe: Option<&str>) -> hide::Result<Answer> {
         match message {
-            lib::Message::V0(v0) => match v0 {
-                V0Message::NewInstance(new_instance) => {
-                    let session_id = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("get timestamp")
-                        .as_millis();
-                    self.new_tab(&new_instance.name, &new_instance.path, session_id);
-                }
-                V0Message::EditFile(edit_file) => {
-                    if let Err(e) = self.edit_file(&edit_file.path) {
-                        eprintln!("edit_file error: {e}");
-                    }
-                }
-                V0Message::FocusPane(focus_pane) => {
-                    if let Err(e) = self.focus_instance_type(focus_pane.typ.as_str().into()) {
-                        eprintln!("error focus pane: {e}");
-                    }
-                }
-                V0Message::WriteToPane(write_to_pane) => {
-                    if let Err(e) = self.write_to_pane(write_to_pane.typ, &write_to_pane.data.0) {
-                        eprintln!("error write to pane: {e}");
-                    }
+            Message::NewInstance(new_instance) => self.new_instance(&new_instance)?,
+            Message::EditFile(edit_file) => {
+                let session_id = self.target_session(edit_file.session);
+                let focus = edit_file.focus();
+                let paths = edit_file.paths;
+                self.editor_action(session_id, &EditorAction::Open { paths }, focus)?
+            }
+            Message::Editor(editor) => {
+                let session_id = self.target_session(editor.session);
+                self.editor_action(session_id, &editor.action, editor.focus())?
+            }
+            Message::RevealFile(reveal_file) => {
+                let session_id = self.target_session(reveal_file.session);
+                return self.reveal_file(
+                    session_id,
+                    &reveal_file.path,
+                    reveal_file.focus(),
+                    cli_pipe,
+                );
+            }
+            Message::FocusPane(focus_pane) => {
+                let session_id = self.target_session(focus_pane.session);
+                self.focus_instance_type(session_id, focus_pane.typ)?
+            }
+            Message::WriteToPane(write_to_pane) => {
+                let session_id = self.target_session(write_to_pane.session);
+                let focus = write_to_pane.focus();
+                self.write_to_pane(session_id, write_to_pane.typ, &write_to_pane.data.0, focus)?
+            }
+            Message::RegisterPane(register_pane) => {
+                if let Some(role) = &register_pane.role {
+                    self.set_pane_type(register_pane.pane, role);
                 }
-            },
+                self.sessions.register_pane(
+                    register_pane.pane,
+                    register_pane.session,
+                    register_pane.pid,
+                    register_pane.role,
+                    now_millis(),
+                );
+                self.save_sessions();
+            }
+            Message::PaneExited(pane_exited) => {
+                self.sessions.pane_exited(
+                    pane_exited.pane,
+                    pane_exited.session,
+                    pane_exited.status,
+                    pane_exited.restarting,
+                    now_millis(),
+                );
+                self.save_sessions();
+            }
+            Message::ListSessions => {
+                let sessions = summarize_sessions(&self.instances, self.focused_session.id);
+                return serde_json::to_value(sessions)
+                    .map(Answer::Now)
+                    .map_err(|e| e.to_string());
+            }
+            Message::ListPanes(list_panes) => {
+                let panes: Vec<PaneSummary> = summarize_panes(&self.instances, list_panes.session)?
+                    .into_iter()
+                    .map(|pane| {
+                        let record = self.sessions.pane(pane.id);
+                        pane.with_record(record)
+                    })
+                    .collect();
+                return serde_json::to_value(panes)
+                    .map(Answer::Now)
+                    .map_err(|e| e.to_string());
+            }
+            Message::LayoutEnv(layout_env) => {
+                let template = Template::parse(&self.layouts.get(layout_env.layout.as_deref())?);
+                return serde_json::to_value(template.env())
+                    .map(Answer::Now)
+                    .map_err(|e| e.to_string());
+            }
         }
 
-        false
+        Ok(Answer::Now(serde_json::Value::Null))
+    }
+
+    // set_session_name picks the state file of the zellij session the plugin runs
+    // in. The registry is restored once the name is known, and saved under the
+    // new name when the session is renamed.
+    fn set_session_name(&mut self, session_name: &str) {
+        let state_file = state_file(&self.state_dir, session_name);
+        if self.state_file.as_ref() == Some(&state_file) {
+            return;
+        }
+
+        let restore = self.state_file.is_none();
+        self.state_file = Some(state_file);
+        if restore {
+            self.load_sessions();
+        } else {
+            self.save_sessions();
+        }
+    }
+
+    // load_sessions restores the session registry saved by a previous instance of
+    // the plugin, the next PaneUpdate reconciles it with the panes that are open.
+    fn load_sessions(&mut self) {
+        let Some(state_file) = &self.state_file else {
+            return;
+        };
+        let json = match std::fs::read_to_string(state_file) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                eprintln!("read session state {state_file}: {e}");
+                return;
+            }
+        };
+
+        match SessionRegistry::from_json(&json) {
+            Ok(sessions) => self.sessions = sessions,
+            Err(e) => eprintln!("{state_file}: {e}"),
+        }
+        // the roles of the panes already known, without waiting for a PaneUpdate
+        for pane in self.instances.values_mut().flatten() {
+            if let Some(role) = self.sessions.role(pane.info.id) {
+                pane.typ = role.clone();
+            }
+        }
+        // sessions that didn't open before the plugin was unloaded never will
+        if self.sessions.forget_expired(now_millis()) {
+            self.save_sessions();
+        }
+    }
+
+    fn save_sessions(&self) {
+        let Some(state_file) = &self.state_file else {
+            return;
+        };
+        let result = self
+            .sessions
+            .to_json()
+            .and_then(|json| std::fs::write(state_file, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("save session state {state_file}: {e}");
+        }
+    }
+
+    // set_pane_type changes the type of a known pane right away, rather than on the
+    // next PaneUpdate.
+    fn set_pane_type(&mut self, pane_id: u32, typ: &PaneType) {
+        let pane = self
+            .instances
+            .values_mut()
+            .flatten()
+            .find(|pane| pane.info.id == pane_id);
+        if let Some(pane) = pane {
+            pane.typ = typ.clone();
+        }
     }
 
-    fn focus_instance_type(&self, typ: PaneType) -> lib::Result<()> {
-        let instance = self.find_instance_by_type(typ)?;
+    // target_session returns the session a message acts on, the one it names or
+    // the focused one.
+    fn target_session(&self, session: Option<u64>) -> u64 {
+        session.unwrap_or(self.focused_session.id)
+    }
+
+    fn focus_instance_type(&self, session_id: u64, typ: PaneType) -> hide::Result<()> {
+        let instance = self.find_instance_by_type(session_id, typ)?;
         focus_pane_with_id(PaneId::Terminal(instance.info.id), true);
 
         Ok(())
     }
 
-    fn new_tab(&self, tab_name: &str, path: &str, session_id: u128) {
-        let mut layout = LAYOUT.to_string();
-        layout = layout.replace("{tab_name}", tab_name);
-        layout = layout.replace("{session_id}", format!("{session_id}").as_str());
-        layout = layout.replace("{path}", path);
+    // new_instance opens a session for the message's path, or switches to the
+    // session already open for it unless a new one is forced.
+    fn new_instance(&mut self, new_instance: &NewInstanceMessage) -> hide::Result<()> {
+        let now = now_millis();
+        // forget sessions that never opened, rather than switching to them
+        if self.sessions.reconcile(&self.instances, now) {
+            self.save_sessions();
+        }
+
+        if !new_instance.force {
+            let existing = self
+                .sessions
+                .find_by_root(&new_instance.path)
+                .map(|record| record.id)
+                .or_else(|| find_session_by_path(&self.instances, &new_instance.path));
+            if let Some(session_id) = existing {
+                return self.focus_session(session_id);
+            }
+        }
+
+        // ids of sessions that are still opening, or were restored from the state
+        // file, are taken as well
+        let session_id = self.session_ids.next(now, |id| {
+            self.instances.contains_key(&id) || self.sessions.get(id).is_some()
+        });
+        self.new_tab(new_instance, session_id)?;
+        let layout = new_instance
+            .layout
+            .as_deref()
+            .unwrap_or(self.layouts.default_name());
+        self.sessions.insert(session_id, &new_instance.path, layout);
+        self.save_sessions();
+
+        Ok(())
+    }
+
+    // focus_session focuses the last focused pane of a session, or its first one.
+    // Sessions that are still opening have no panes yet, they fail until their
+    // tab shows up.
+    fn focus_session(&self, session_id: u64) -> hide::Result<()> {
+        let panes = self
+            .instances
+            .get(&session_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let pane = panes
+            .iter()
+            .find(|pane| pane.info.is_focused)
+            .or(panes.first())
+            .ok_or_else(|| format!("session {session_id} has no panes yet, it is still opening"))?;
+        focus_pane_with_id(PaneId::Terminal(pane.info.id), true);
+
+        Ok(())
+    }
+
+    fn scan_projects(&mut self) {
+        if self.project_roots.is_empty() {
+            eprintln!("scan projects: no project_roots configured");
+            self.dashboard.set_projects(vec![]);
+            return;
+        }
+
+        let command = scan_projects_command(&self.project_roots);
+        let command: Vec<&str> = command.iter().map(String::as_str).collect();
+        run_command(
+            &command,
+            BTreeMap::from([("source".to_string(), SCAN_PROJECTS.to_string())]),
+        );
+    }
+
+    // open_project opens a project picked in the dashboard with the default layout.
+    fn open_project(&mut self, path: &str) -> hide::Result<()> {
+        let name = Path::new(path)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or_else(|| format!("invalid project path {path}"))?;
+        self.new_instance(&NewInstanceMessage {
+            name,
+            path: path.into(),
+            layout: None,
+            vars: BTreeMap::new(),
+            force: false,
+        })
+    }
+
+    fn new_tab(&self, new_instance: &NewInstanceMessage, session_id: u64) -> hide::Result<()> {
+        let template = Template::parse(&self.layouts.get(new_instance.layout.as_deref())?);
+
+        let unused = template.unused(
+            new_instance
+                .vars
+                .keys()
+                .map(String::as_str)
+                .filter(|k| k.starts_with("var.")),
+        );
+        if !unused.is_empty() {
+            return Err(format!("unused layout variables: {}", unused.join(", ")));
+        }
+
+        let mut vars = new_instance.vars.clone();
+        // the cli looks the branch up, instances opened from the picker have none
+        vars.entry("git_branch".into()).or_default();
+        vars.insert("tab_name".into(), new_instance.name.clone());
+        vars.insert("session_id".into(), session_id.to_string());
+        vars.insert("path".into(), new_instance.path.clone());
+        vars.insert(
+            "project_name".into(),
+            Path::new(&new_instance.path)
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        );
+
+        new_tabs_with_layout(&template.render(&vars)?);
+
+        Ok(())
+    }
+
+    // reveal_file navigates the file explorer of session_id to path, using the
+    // explorer configured for the session's layout. A cli pipe asking to reveal
+    // through the explorer's command is answered once the command finishes.
+    fn reveal_file(
+        &self,
+        session_id: u64,
+        path: &str,
+        focus: bool,
+        cli_pipe: Option<&str>,
+    ) -> hide::Result<Answer> {
+        let layout = self
+            .sessions
+            .get(session_id)
+            .map(|record| record.layout.as_str());
+
+        match self.explorers.get(layout).reveal(path, session_id) {
+            ExplorerCommand::Keys(keys) => {
+                self.write_to_pane(session_id, PaneType::FileExplorer, &keys, focus)?;
+                Ok(Answer::Now(serde_json::Value::Null))
+            }
+            ExplorerCommand::Run(command) => {
+                let explorer = self.find_instance_by_type(session_id, PaneType::FileExplorer)?;
+                if focus {
+                    focus_pane_with_id(PaneId::Terminal(explorer.info.id), true);
+                }
 
-        new_tabs_with_layout(&layout);
+                let mut context = BTreeMap::from([
+                    ("source".to_string(), REVEAL_FILE.to_string()),
+                    ("command".to_string(), command[0].clone()),
+                ]);
+                if let Some(pipe_id) = cli_pipe {
+                    context.insert("pipe_id".to_string(), pipe_id.to_string());
+                }
+                let command: Vec<&str> = command.iter().map(String::as_str).collect();
+                run_command(&command, context);
+                Ok(match cli_pipe {
+                    Some(_) => Answer::Later,
+                    None => Answer::Now(serde_json::Value::Null),
+                })
+            }
+        }
     }
 
-    fn find_instance_by_type(&self, typ: PaneType) -> lib::Result<&InstancePane> {
-        let focused_session = &self.focused_session;
-        let focused_session_id = &focused_session.id;
+    fn find_instance_by_type(&self, session_id: u64, typ: PaneType) -> hide::Result<&InstancePane> {
         let instances = self
             .instances
-            .get(focused_session_id)
-            .ok_or_else(|| format!("invalid session id: {focused_session_id}"))?;
+            .get(&session_id)
+            .ok_or_else(|| format!("invalid session id: {session_id}"))?;
 
-        let instance = instances.iter().find(|p| p.typ.eq(&typ)).ok_or_else(|| {
-            format!("invalid instance type {typ:?} for session {focused_session_id}")
-        })?;
+        let instance = instances
+            .iter()
+            .find(|p| p.typ.eq(&typ))
+            .ok_or_else(|| format!("invalid instance type {typ:?} for session {session_id}"))?;
 
         Ok(instance)
     }
 
-    fn write_to_pane(&self, typ: PaneType, w: &[WriteToPane]) -> lib::Result<()> {
-        let instance = self.find_instance_by_type(typ)?;
+    // write_to_pane writes w to the pane of type typ in session_id, without focus
+    // the pane is written to in the background.
+    fn write_to_pane(
+        &self,
+        session_id: u64,
+        typ: PaneType,
+        w: &[WriteToPane],
+        focus: bool,
+    ) -> hide::Result<()> {
+        let instance = self.find_instance_by_type(session_id, typ)?;
         let pane_id = PaneId::Terminal(instance.info.id);
-        focus_pane_with_id(pane_id, true);
+        if focus {
+            focus_pane_with_id(pane_id, true);
+        }
         for w in w {
             match w {
                 WriteToPane::Bytes(b) => write_to_pane_id(b.to_vec(), pane_id),
@@ -197,19 +591,52 @@ impl State {
         Ok(())
     }
 
-    fn edit_file(&self, path: &str) -> lib::Result<()> {
-        self.write_to_pane(PaneType::Editor, &[
-            // Write Esc to go back to normal mode
-            WriteToPane::Escape,
-            WriteToPane::String(format!(":o {}", path)),
-            // Write Enter to confirm command
-            WriteToPane::Enter,
-        ])
+    // editor_action types the keys for action into the editor pane of session_id,
+    // in the language of the editor configured for the session's layout.
+    fn editor_action(
+        &self,
+        session_id: u64,
+        action: &EditorAction,
+        focus: bool,
+    ) -> hide::Result<()> {
+        let layout = self
+            .sessions
+            .get(session_id)
+            .map(|record| record.layout.as_str());
+        let keys = self.editors.get(layout).keys(action);
+        self.write_to_pane(session_id, PaneType::Editor, &keys, focus)
     }
 }
 
+fn now_millis() -> u64 {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("get timestamp")
+        .as_millis();
+    u64::try_from(millis).expect("timestamp in milliseconds fits a u64")
+}
+
 impl ZellijPlugin for State {
-    fn load(&mut self, _configuration: std::collections::BTreeMap<String, String>) {
+    fn load(&mut self, configuration: BTreeMap<String, String>) {
+        self.layouts = Layouts::from_config(&configuration, LAYOUT).unwrap_or_else(|e| {
+            eprintln!("layout configuration error: {e}");
+            Layouts::new(LAYOUT)
+        });
+        self.pane_rules = PaneRules::from_config(&configuration).unwrap_or_else(|e| {
+            eprintln!("pane_rules configuration error: {e}");
+            PaneRules::default()
+        });
+        self.editors = Editors::from_config(&configuration).unwrap_or_else(|e| {
+            eprintln!("editor configuration error: {e}");
+            Editors::default()
+        });
+        self.explorers = Explorers::from_config(&configuration).unwrap_or_else(|e| {
+            eprintln!("file_explorer configuration error: {e}");
+            Explorers::default()
+        });
+        self.project_roots = project_roots(&configuration);
+        self.state_dir = state_dir(&configuration);
+
         request_permission(&[
             PermissionType::ChangeApplicationState,
             PermissionType::ReadApplicationState,
@@ -221,6 +648,9 @@ impl ZellijPlugin for State {
             EventType::PermissionRequestResult,
             EventType::PaneUpdate,
             EventType::TabUpdate,
+            EventType::ModeUpdate,
+            EventType::Key,
+            EventType::RunCommandResult,
         ]);
     }
 
@@ -262,7 +692,22 @@ impl ZellijPlugin for State {
         render
     }
 
-    fn render(&mut self, _rows: usize, _cols: usize) {}
+    fn render(&mut self, rows: usize, cols: usize) {
+        let dashboard_rows = Dashboard::rows(&self.instances, self.focused_session.id);
+        for line in self.dashboard.render(&dashboard_rows, rows, cols) {
+            println!("{line}");
+        }
+    }
+}
+
+// answer_cli_pipe sends the response to a message to the cli pipe it came from
+// and lets hide-cli exit.
+fn answer_cli_pipe(pipe_id: &str, result: hide::Result<serde_json::Value>) {
+    match encode_response(&Response::from(result)) {
+        Ok(output) => cli_pipe_output(pipe_id, &format!("{output}\n")),
+        Err(e) => eprintln!("handle_pipe_message response error: {e}"),
+    }
+    unblock_cli_pipe_input(pipe_id);
 }
 
 register_plugin!(State);
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use zellij_tile::prelude::{BareKey, KeyModifier, KeyWithModifier};
+
+use crate::dashboard::{BOLD, DIM, RESET, REVERSE, truncate};
+use crate::fuzzy_filter;
+
+const HELP: &str = "type to filter  ↑/↓ move  enter open  esc back";
+
+// Prints the directories right under every root given as an argument. Roots
+// come from the plugin configuration and run_command doesn't go through a
+// shell, so `~/` is expanded here.
+const SCAN_PROJECTS: &str = r#"for root in "$@"; do
+  case "$root" in "~/"*) root="$HOME/${root#"~/"}" ;; esac
+  find "$root" -mindepth 1 -maxdepth 1 -type d -not -name '.*' 2>/dev/null
+done"#;
+
+// project_roots reads the `project_roots` plugin configuration, one directory
+// per line.
+pub fn project_roots(config: &BTreeMap<String, String>) -> Vec<String> {
+    config
+        .get("project_roots")
+        .map(|roots| {
+            roots
+                .lines()
+                .map(str::trim)
+                .filter(|root| !root.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn scan_projects_command(roots: &[String]) -> Vec<String> {
+    let mut command = vec!["sh".into(), "-c".into(), SCAN_PROJECTS.into(), "sh".into()];
+    command.extend(roots.iter().cloned());
+    command
+}
+
+pub fn parse_scan_output(stdout: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .map(|line| line.trim_end_matches('/'))
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PickerAction {
+    Render,
+    Open(String),
+    Close,
+}
+
+// ProjectPicker fuzzy filters the project directories found under the
+// configured roots.
+#[derive(Debug, Default)]
+pub struct ProjectPicker {
+    query: String,
+    selected: usize,
+    projects: Vec<String>,
+    scanned: bool,
+}
+
+impl ProjectPicker {
+    pub fn set_projects(&mut self, mut projects: Vec<String>) {
+        projects.sort();
+        projects.dedup();
+        self.projects = projects;
+        self.scanned = true;
+    }
+
+    fn matches(&self) -> Vec<&str> {
+        fuzzy_filter(&self.query, &self.projects)
+            .into_iter()
+            .map(|idx| self.projects[idx].as_str())
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, key: &KeyWithModifier) -> Option<PickerAction> {
+        let ctrl = key.key_modifiers.contains(&KeyModifier::Ctrl);
+        let alt = key.key_modifiers.contains(&KeyModifier::Alt);
+
+        match key.bare_key {
+            BareKey::Down | BareKey::Tab => self.move_selection(1),
+            BareKey::Up => self.move_selection(-1),
+            BareKey::Char('n') if ctrl => self.move_selection(1),
+            BareKey::Char('p') if ctrl => self.move_selection(-1),
+            BareKey::Enter => {
+                let selected = self.matches().get(self.selected)?.to_string();
+                Some(PickerAction::Open(selected))
+            }
+            BareKey::Esc => Some(PickerAction::Close),
+            BareKey::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+                Some(PickerAction::Render)
+            }
+            BareKey::Char(ch) if !ctrl && !alt => {
+                self.query.push(ch);
+                self.selected = 0;
+                Some(PickerAction::Render)
+            }
+            _ => None,
+        }
+    }
+
+    fn move_selection(&mut self, by: isize) -> Option<PickerAction> {
+        let last = self.matches().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(by).min(last);
+        Some(PickerAction::Render)
+    }
+
+    pub fn render(&mut self, height: usize, width: usize) -> Vec<String> {
+        self.selected = self.selected.min(self.matches().len().saturating_sub(1));
+        let matches = self.matches();
+
+        let prompt = format!("open project: {}", self.query);
+        let mut lines = vec![format!("{BOLD}{}{RESET}", truncate(&prompt, width))];
+
+        if !self.scanned {
+            lines.push(format!("{DIM}{}{RESET}", truncate("scanning...", width)));
+        } else if matches.is_empty() {
+            lines.push(format!("{DIM}{}{RESET}", truncate("no projects", width)));
+        }
+
+        // prompt and help take a line each
+        let visible = height.saturating_sub(2).max(1);
+        let offset = (self.selected + 1).saturating_sub(visible);
+        for (idx, project) in matches.iter().enumerate().skip(offset).take(visible) {
+            let text = truncate(project, width);
+            if idx == self.selected {
+                lines.push(format!("{REVERSE}{text}{RESET}"));
+            } else {
+                lines.push(format!("{text}{RESET}"));
+            }
+        }
+
+        if height > 1 {
+            lines.push(format!("{DIM}{}{RESET}", truncate(HELP, width)));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> ProjectPicker {
+        let mut picker = ProjectPicker::default();
+        picker.set_projects(vec![
+            "/src/zellij".into(),
+            "/src/hide".into(),
+            "/work/shadow".into(),
+            "/src/hide".into(),
+        ]);
+        picker
+    }
+
+    fn key(bare_key: BareKey) -> KeyWithModifier {
+        KeyWithModifier::new(bare_key)
+    }
+
+    fn type_query(picker: &mut ProjectPicker, query: &str) {
+        for ch in query.chars() {
+            picker.handle_key(&key(BareKey::Char(ch)));
+        }
+    }
+
+    #[test]
+    fn test_project_roots() {
+        let config = BTreeMap::from([(
+            "project_roots".to_string(),
+            "\n  ~/src\n\n  /work  \n".to_string(),
+        )]);
+
+        assert_eq!(project_roots(&config), vec!["~/src", "/work"]);
+        assert!(project_roots(&BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_scan_projects_command() {
+        let command = scan_projects_command(&["~/src".into(), "/work".into()]);
+
+        assert_eq!(command[..2], ["sh", "-c"]);
+        assert_eq!(command[3..], ["sh", "~/src", "/work"]);
+    }
+
+    #[test]
+    fn test_parse_scan_output() {
+        let projects = parse_scan_output(b"/src/hide\n/src/zellij/\n\n");
+
+        assert_eq!(projects, vec!["/src/hide", "/src/zellij"]);
+    }
+
+    #[test]
+    fn test_picker_filter_and_open() {
+        let mut picker = picker();
+        type_query(&mut picker, "hd");
+
+        assert_eq!(picker.matches(), vec!["/src/hide", "/work/shadow"]);
+        assert_eq!(
+            picker.handle_key(&key(BareKey::Down)),
+            Some(PickerAction::Render)
+        );
+        assert_eq!(
+            picker.handle_key(&key(BareKey::Enter)),
+            Some(PickerAction::Open("/work/shadow".into()))
+        );
+    }
+
+    #[test]
+    fn test_picker_backspace_resets_selection() {
+        let mut picker = picker();
+        picker.handle_key(&key(BareKey::Char('n')).with_ctrl_modifier());
+        assert_eq!(picker.selected, 1);
+
+        type_query(&mut picker, "zx");
+        assert!(picker.matches().is_empty());
+        assert_eq!(picker.handle_key(&key(BareKey::Enter)), None);
+
+        picker.handle_key(&key(BareKey::Backspace));
+        assert_eq!(picker.query, "z");
+        assert_eq!(picker.selected, 0);
+        assert_eq!(
+            picker.handle_key(&key(BareKey::Enter)),
+            Some(PickerAction::Open("/src/zellij".into()))
+        );
+    }
+
+    #[test]
+    fn test_picker_selection_bounds() {
+        let mut picker = picker();
+        picker.handle_key(&key(BareKey::Up));
+        assert_eq!(picker.selected, 0);
+
+        for _ in 0..5 {
+            picker.handle_key(&key(BareKey::Down));
+        }
+        assert_eq!(picker.selected, 2);
+    }
+
+    #[test]
+    fn test_picker_close() {
+        let mut picker = picker();
+
+        assert_eq!(
+            picker.handle_key(&key(BareKey::Esc)),
+            Some(PickerAction::Close)
+        );
+        assert_eq!(
+            picker.handle_key(&key(BareKey::Char('x')).with_alt_modifier()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_picker_render() {
+        let mut picker = picker();
+        type_query(&mut picker, "src");
+        let lines = picker.render(10, 80);
+
+        assert_eq!(
+            lines,
+            vec![
+                format!("{BOLD}open project: src{RESET}"),
+                format!("{REVERSE}/src/hide{RESET}"),
+                format!("/src/zellij{RESET}"),
+                format!("{DIM}{HELP}{RESET}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_picker_render_scanning() {
+        let mut picker = ProjectPicker::default();
+        let lines = picker.render(10, 80);
+
+        assert_eq!(lines[1], format!("{DIM}scanning...{RESET}"));
+    }
+}
@@ -0,0 +1,1255 @@
+// The wire format of pipe messages is a single protocol version character
+// followed by the version specific body:
+//
+//   v0: `0<command>;<key>=<value>;...`
+//       Kept for hand written payloads (e.g. zellij keybindings). Values can't
+//       contain `;`, there is no escaping.
+//   v1: `1<json>`
+//       A JSON object tagged by `command`, e.g.
+//       `1{"command":"edit_file","paths":[{"path":"/tmp/a;b=c"}]}`. Escaping follows JSON
+//       string rules, so any value can be sent. This is what hide-cli sends,
+//       always built with `encode_pipe_message`.
+//
+// Messages coming from a CLI pipe are answered with a single JSON `Response`
+// line on the pipe's output, regardless of the message version.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EditorAction, PaneType, Result, WriteToPane, WritesToPane};
+
+pub const PROTOCOL_VERSION: char = '1';
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewInstanceMessage {
+    pub name: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    // Extra layout variables: user supplied `var.*`, the caller's `env.*` the
+    // layout uses and `git_branch`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    // Open another session even if one is already open for path.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub force: bool,
+}
+
+// Messages acting on a session take an optional `session` id, without it they
+// act on the focused session. hide-cli fills it in from `SESSION_ID`, so
+// commands run from a background pane or another tab reach their own session.
+//
+// Messages writing into a pane take an optional `focus`, whether the pane gets
+// focused. Without it, commands bringing up something to look at focus and
+// background ones, like saving all files, don't.
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditFileMessage {
+    pub paths: Vec<FileTarget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<bool>,
+}
+
+impl EditFileMessage {
+    pub fn focus(&self) -> bool {
+        self.focus.unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditorMessage {
+    #[serde(flatten)]
+    pub action: EditorAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<bool>,
+}
+
+impl EditorMessage {
+    pub fn focus(&self) -> bool {
+        self.focus.unwrap_or(match self.action {
+            EditorAction::Open { .. } | EditorAction::Goto { .. } => true,
+            EditorAction::SaveAll | EditorAction::ReloadAll => false,
+        })
+    }
+}
+
+impl From<EditorAction> for EditorMessage {
+    fn from(action: EditorAction) -> Self {
+        EditorMessage {
+            action,
+            session: None,
+            focus: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevealFileMessage {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<bool>,
+}
+
+impl RevealFileMessage {
+    pub fn focus(&self) -> bool {
+        self.focus.unwrap_or(true)
+    }
+}
+
+// FileTarget is a file to open at an optional position. v1 messages carry it as
+// `{"path":...,"line":...,"column":...}`, the cli arguments and v0 payloads the
+// way compilers and grep tools print locations: `path`, `path:line` or
+// `path:line:column`. Anything after the column, like the matched text of
+// `rg --vimgrep`, is ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileTarget {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+}
+
+impl From<&str> for FileTarget {
+    fn from(value: &str) -> Self {
+        let parts: Vec<&str> = value.split(':').collect();
+        let number = |idx: usize| {
+            parts
+                .get(idx)
+                .filter(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|part| part.parse().ok())
+        };
+
+        // the path ends before the first numeric part, paths can contain `:` too
+        match (1..parts.len()).find(|idx| number(*idx).is_some()) {
+            Some(idx) => FileTarget {
+                path: parts[..idx].join(":"),
+                line: number(idx),
+                column: number(idx + 1),
+            },
+            None => FileTarget {
+                path: value.into(),
+                line: None,
+                column: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for FileTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusPaneMessage {
+    #[serde(rename = "type")]
+    pub typ: PaneType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WriteToPaneMessage {
+    #[serde(rename = "type")]
+    pub typ: PaneType,
+    pub data: WritesToPane,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<bool>,
+}
+
+impl WriteToPaneMessage {
+    pub fn focus(&self) -> bool {
+        self.focus.unwrap_or(true)
+    }
+}
+
+// RegisterPaneMessage is sent by `hide-cli run` when it starts the program it
+// supervises, pane is the zellij pane it runs in. It registers the pane's type,
+// session and pid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterPaneMessage {
+    pub pane: u32,
+    pub pid: u32,
+    // The type of the pane, declared with `hide-cli run --role` or HIDE_ROLE.
+    // hide-cli can't tell the type of an undeclared pane, without a role the
+    // plugin keeps classifying it with the pane rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<PaneType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+// PaneExitedMessage is sent by `hide-cli run` when its program exits, with the
+// exit code or 128 + the signal that killed it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneExitedMessage {
+    pub pane: u32,
+    pub status: i32,
+    // The program is started again after a crash.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub restarting: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListPanesMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+// LayoutEnvMessage asks for the environment variables a layout uses, the
+// `{env.NAME}` placeholders hide-cli has to fill in for new_instance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutEnvMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Message {
+    NewInstance(NewInstanceMessage),
+    EditFile(EditFileMessage),
+    // Any editor action, edit_file is a shorthand for `open`.
+    Editor(EditorMessage),
+    // Navigates the file explorer pane to path.
+    RevealFile(RevealFileMessage),
+    FocusPane(FocusPaneMessage),
+    WriteToPane(WriteToPaneMessage),
+    // Lifecycle of the panes supervised by `hide-cli run`.
+    RegisterPane(RegisterPaneMessage),
+    PaneExited(PaneExitedMessage),
+    // Queries, answered with a list of `SessionSummary`, `PaneSummary` and
+    // environment variable names.
+    ListSessions,
+    ListPanes(ListPanesMessage),
+    LayoutEnv(LayoutEnvMessage),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Ok {
+        #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+        data: serde_json::Value,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl From<Result<serde_json::Value>> for Response {
+    fn from(value: Result<serde_json::Value>) -> Self {
+        match value {
+            Ok(data) => Response::Ok { data },
+            Err(message) => Response::Error { message },
+        }
+    }
+}
+
+macro_rules! extract_message_key {
+    ($kvs:expr, $key:expr) => {
+        get_kv($kvs, $key)
+            .ok_or(format!("{} is required", $key))?
+            .into()
+    };
+}
+
+// get_kv returns the value of key, the last one when it's repeated.
+fn get_kv<'a>(kvs: &[(&str, &'a str)], key: &str) -> Option<&'a str> {
+    kvs.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+impl Message {
+    // from_kvs builds a message out of its command name and `key=value` arguments,
+    // the shape used by both v0 payloads and `hide-cli pipe`. Keys taking a list,
+    // like edit_file's `path`, are repeated.
+    pub fn from_kvs(command: &str, kvs: &[(&str, &str)]) -> Result<Message> {
+        let message = match command {
+            "new_instance" => Message::NewInstance(NewInstanceMessage {
+                name: extract_message_key!(kvs, "name"),
+                path: extract_message_key!(kvs, "path"),
+                layout: get_kv(kvs, "layout").map(String::from),
+                vars: kvs
+                    .iter()
+                    .filter(|(k, _)| k.starts_with("var."))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                force: parse_flag(kvs, "force")?,
+            }),
+            "edit_file" => Message::EditFile(EditFileMessage {
+                paths: file_targets(kvs)?,
+                session: parse_session(kvs)?,
+                focus: parse_optional_flag(kvs, "focus")?,
+            }),
+            "editor" => {
+                let action: &str = extract_message_key!(kvs, "action");
+                let action = match action {
+                    "open" => EditorAction::Open {
+                        paths: file_targets(kvs)?,
+                    },
+                    "goto" => EditorAction::Goto {
+                        line: parse_number(kvs, "line")?.ok_or("line is required")?,
+                        column: parse_number(kvs, "column")?,
+                    },
+                    "save_all" => EditorAction::SaveAll,
+                    "reload_all" => EditorAction::ReloadAll,
+                    _ => {
+                        return Err(format!(
+                            "invalid editor action {action}, valid: open, goto, save_all, reload_all"
+                        ));
+                    }
+                };
+                Message::Editor(EditorMessage {
+                    action,
+                    session: parse_session(kvs)?,
+                    focus: parse_optional_flag(kvs, "focus")?,
+                })
+            }
+            "reveal_file" => Message::RevealFile(RevealFileMessage {
+                path: extract_message_key!(kvs, "path"),
+                session: parse_session(kvs)?,
+                focus: parse_optional_flag(kvs, "focus")?,
+            }),
+            "focus_pane" => Message::FocusPane(FocusPaneMessage {
+                typ: extract_message_key!(kvs, "type"),
+                session: parse_session(kvs)?,
+            }),
+            "write_to_pane" => Message::WriteToPane(WriteToPaneMessage {
+                typ: extract_message_key!(kvs, "type"),
+                data: {
+                    let data_str: &str = extract_message_key!(kvs, "data");
+                    // raw data is typed as is, without <symbol> parsing
+                    if parse_flag(kvs, "raw")? {
+                        WritesToPane(vec![WriteToPane::String(data_str.into())])
+                    } else {
+                        data_str.try_into()?
+                    }
+                },
+                session: parse_session(kvs)?,
+                focus: parse_optional_flag(kvs, "focus")?,
+            }),
+            "register_pane" => Message::RegisterPane(RegisterPaneMessage {
+                pane: parse_number(kvs, "pane")?.ok_or("pane is required")?,
+                pid: parse_number(kvs, "pid")?.ok_or("pid is required")?,
+                role: get_kv(kvs, "role").map(PaneType::from),
+                session: parse_session(kvs)?,
+            }),
+            "pane_exited" => Message::PaneExited(PaneExitedMessage {
+                pane: parse_number(kvs, "pane")?.ok_or("pane is required")?,
+                status: parse_number(kvs, "status")?.ok_or("status is required")?,
+                restarting: parse_flag(kvs, "restarting")?,
+                session: parse_session(kvs)?,
+            }),
+            "list_sessions" => Message::ListSessions,
+            "list_panes" => Message::ListPanes(ListPanesMessage {
+                session: parse_session(kvs)?,
+            }),
+            "layout_env" => Message::LayoutEnv(LayoutEnvMessage {
+                layout: get_kv(kvs, "layout").map(String::from),
+            }),
+            _ => return Err(format!("invalid protocol message {command}")),
+        };
+
+        Ok(message)
+    }
+
+    // set_default_session targets session with a message acting on a session,
+    // unless it already names one. Other messages are left as they are, a
+    // list_panes session filters rather than targets.
+    pub fn set_default_session(&mut self, session: u64) {
+        let target = match self {
+            Message::EditFile(m) => &mut m.session,
+            Message::Editor(m) => &mut m.session,
+            Message::RevealFile(m) => &mut m.session,
+            Message::FocusPane(m) => &mut m.session,
+            Message::WriteToPane(m) => &mut m.session,
+            Message::RegisterPane(m) => &mut m.session,
+            Message::PaneExited(m) => &mut m.session,
+            Message::NewInstance(_)
+            | Message::ListSessions
+            | Message::ListPanes(_)
+            | Message::LayoutEnv(_) => return,
+        };
+        target.get_or_insert(session);
+    }
+}
+
+fn parse_session(kvs: &[(&str, &str)]) -> Result<Option<u64>> {
+    get_kv(kvs, "session")
+        .map(|s| s.parse().map_err(|_| format!("invalid session id: {s}")))
+        .transpose()
+}
+
+// file_targets reads the repeated `path` argument, at least one is required.
+fn file_targets(kvs: &[(&str, &str)]) -> Result<Vec<FileTarget>> {
+    let paths: Vec<FileTarget> = kvs
+        .iter()
+        .filter(|(k, _)| *k == "path")
+        .map(|(_, v)| FileTarget::from(*v))
+        .collect();
+    if paths.is_empty() {
+        return Err("path is required".into());
+    }
+
+    Ok(paths)
+}
+
+fn parse_number<T: FromStr>(kvs: &[(&str, &str)], key: &str) -> Result<Option<T>> {
+    get_kv(kvs, key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("invalid {key} value {value}, expected a number"))
+        })
+        .transpose()
+}
+
+// parse_flag reads an optional `true`/`false` argument, missing flags are false.
+fn parse_flag(kvs: &[(&str, &str)], key: &str) -> Result<bool> {
+    Ok(parse_optional_flag(kvs, key)?.unwrap_or(false))
+}
+
+fn parse_optional_flag(kvs: &[(&str, &str)], key: &str) -> Result<Option<bool>> {
+    match get_kv(kvs, key) {
+        None => Ok(None),
+        Some("true") => Ok(Some(true)),
+        Some("false") => Ok(Some(false)),
+        Some(value) => Err(format!(
+            "invalid {key} value {value}, expected true or false"
+        )),
+    }
+}
+
+pub fn parse_pipe_message(payload: &str) -> Result<Message> {
+    let version = payload.chars().next().ok_or("invalid payload len 0")?;
+    if !version.is_numeric() {
+        return Err("first character must be a protocol version".into());
+    }
+
+    match version {
+        '0' => parse_v0_message(&payload[1..]),
+        '1' => parse_v1_message(&payload[1..]),
+        _ => Err(format!(
+            "invalid protocol version {version}, supported: '0', '1'"
+        )),
+    }
+}
+
+pub fn encode_pipe_message(message: &Message) -> Result<String> {
+    let body = serde_json::to_string(message).map_err(|e| format!("encode message: {e}"))?;
+    Ok(format!("{PROTOCOL_VERSION}{body}"))
+}
+
+pub fn encode_response(response: &Response) -> Result<String> {
+    serde_json::to_string(response).map_err(|e| format!("encode response: {e}"))
+}
+
+pub fn parse_response(output: &str) -> Result<Response> {
+    serde_json::from_str(output).map_err(|e| format!("invalid response: {e}"))
+}
+
+fn parse_v0_message(payload: &str) -> Result<Message> {
+    // every part is terminated by a `;`, whatever follows the last one is ignored
+    let mut parts: Vec<&str> = payload.split(';').collect();
+    parts.pop();
+
+    let command = parts
+        .first()
+        .ok_or("command is required after protocol version")?;
+
+    let mut kvs = vec![];
+    for part in parts.iter().skip(1) {
+        let idx = part.find("=").ok_or("no kv pair in command args")?;
+
+        let k = &part[..idx];
+        let v = &part[idx + 1..];
+
+        kvs.push((k, v));
+    }
+
+    Message::from_kvs(command, &kvs)
+}
+
+fn parse_v1_message(payload: &str) -> Result<Message> {
+    serde_json::from_str(payload).map_err(|e| format!("invalid v1 message: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pipe_message_new_instance_with_path() {
+        let payload = "0new_instance;name=test_instance;path=/tmp;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        match message {
+            Message::NewInstance(instance) => {
+                assert_eq!(instance.name, "test_instance");
+                assert_eq!(instance.path, "/tmp");
+                assert_eq!(instance.layout, None);
+                assert!(instance.vars.is_empty());
+                assert!(!instance.force);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_new_instance_with_vars() {
+        let payload = "0new_instance;name=hide;path=/tmp;layout=nu;var.shell=nu;var.x=1;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        match message {
+            Message::NewInstance(instance) => {
+                assert_eq!(instance.layout, Some("nu".into()));
+                assert_eq!(
+                    instance.vars,
+                    BTreeMap::from([
+                        ("var.shell".into(), "nu".into()),
+                        ("var.x".into(), "1".into())
+                    ])
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_new_instance_force() {
+        let payload = "0new_instance;name=hide;path=/tmp;force=true;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        match message {
+            Message::NewInstance(instance) => assert!(instance.force),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_new_instance_invalid_force() {
+        let payload = "0new_instance;name=hide;path=/tmp;force=yes;";
+        let message = parse_pipe_message(payload);
+
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid force value yes, expected true or false".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_edit_file() {
+        let payload = "0edit_file;path=/tmp/foo.txt;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        match message {
+            Message::EditFile(instance) => {
+                assert_eq!(instance.paths, vec![FileTarget::from("/tmp/foo.txt")]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_edit_file_multiple_paths() {
+        let payload = "0edit_file;path=src/main.rs:10:5;path=README.md;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::EditFile(EditFileMessage {
+                paths: vec![
+                    FileTarget {
+                        path: "src/main.rs".into(),
+                        line: Some(10),
+                        column: Some(5),
+                    },
+                    FileTarget {
+                        path: "README.md".into(),
+                        line: None,
+                        column: None,
+                    },
+                ],
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_edit_file_missing_path() {
+        let payload = "0edit_file;line=1;";
+        let message = parse_pipe_message(payload);
+
+        assert_eq!(message.err().unwrap(), "path is required".to_string());
+    }
+
+    #[test]
+    fn test_parse_pipe_message_reveal_file() {
+        let payload = "0reveal_file;path=/tmp/a:1.rs;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::RevealFile(RevealFileMessage {
+                path: "/tmp/a:1.rs".into(),
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_editor() {
+        let cases = [
+            (
+                "0editor;action=open;path=a.rs:1;path=b.rs;",
+                EditorAction::Open {
+                    paths: vec!["a.rs:1".into(), "b.rs".into()],
+                },
+            ),
+            (
+                "0editor;action=goto;line=12;column=4;",
+                EditorAction::Goto {
+                    line: 12,
+                    column: Some(4),
+                },
+            ),
+            ("0editor;action=save_all;", EditorAction::SaveAll),
+            ("0editor;action=reload_all;", EditorAction::ReloadAll),
+        ];
+
+        for (payload, action) in cases {
+            assert_eq!(
+                parse_pipe_message(payload).unwrap(),
+                Message::Editor(action.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_focus() {
+        let payload = "0write_to_pane;type=terminal;data=cargo test<enter>;focus=false;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        match message {
+            Message::WriteToPane(write_to_pane) => assert!(!write_to_pane.focus()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_invalid_focus() {
+        let payload = "0reveal_file;path=a;focus=no;";
+        let message = parse_pipe_message(payload);
+
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid focus value no, expected true or false".to_string()
+        );
+    }
+
+    #[test]
+    fn test_message_focus_defaults() {
+        let focus = |payload: &str| match parse_pipe_message(payload).unwrap() {
+            Message::EditFile(m) => m.focus(),
+            Message::Editor(m) => m.focus(),
+            Message::RevealFile(m) => m.focus(),
+            Message::WriteToPane(m) => m.focus(),
+            _ => unreachable!(),
+        };
+
+        assert!(focus("0edit_file;path=a;"));
+        assert!(focus("0editor;action=goto;line=1;"));
+        assert!(!focus("0editor;action=save_all;"));
+        assert!(focus("0editor;action=save_all;focus=true;"));
+        assert!(!focus("0editor;action=reload_all;"));
+        assert!(focus("0reveal_file;path=a;"));
+        assert!(!focus("0reveal_file;path=a;focus=false;"));
+        assert!(focus("0write_to_pane;type=terminal;data=a;"));
+    }
+
+    #[test]
+    fn test_parse_pipe_message_v1_editor() {
+        let payload = r#"1{"command":"editor","action":"goto","line":3,"focus":false}"#;
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::Editor(EditorMessage {
+                action: EditorAction::Goto {
+                    line: 3,
+                    column: None
+                },
+                focus: Some(false),
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_editor_invalid() {
+        let cases = [
+            (
+                "0editor;action=quit;",
+                "invalid editor action quit, valid: open, goto, save_all, reload_all",
+            ),
+            ("0editor;action=goto;", "line is required"),
+            (
+                "0editor;action=goto;line=x;",
+                "invalid line value x, expected a number",
+            ),
+            ("0editor;action=open;", "path is required"),
+        ];
+
+        for (payload, error) in cases {
+            assert_eq!(
+                parse_pipe_message(payload).err().unwrap(),
+                error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_target_from_str() {
+        let target = |path: &str, line, column| FileTarget {
+            path: path.into(),
+            line,
+            column,
+        };
+
+        assert_eq!(FileTarget::from("a.rs"), target("a.rs", None, None));
+        assert_eq!(FileTarget::from("a.rs:3"), target("a.rs", Some(3), None));
+        assert_eq!(
+            FileTarget::from("a.rs:3:7"),
+            target("a.rs", Some(3), Some(7))
+        );
+        // cargo and rg --vimgrep
+        assert_eq!(
+            FileTarget::from("a.rs:3:7:"),
+            target("a.rs", Some(3), Some(7))
+        );
+        assert_eq!(
+            FileTarget::from("a.rs:3:7:let x = 1;"),
+            target("a.rs", Some(3), Some(7))
+        );
+        assert_eq!(
+            FileTarget::from("/tmp/a:b/c.rs:12"),
+            target("/tmp/a:b/c.rs", Some(12), None)
+        );
+        assert_eq!(
+            FileTarget::from("/tmp/ünï:x"),
+            target("/tmp/ünï:x", None, None)
+        );
+        assert_eq!(FileTarget::from("12:3"), target("12", Some(3), None));
+    }
+
+    #[test]
+    fn test_file_target_display() {
+        for target in ["a.rs", "a.rs:3", "a.rs:3:7", "/tmp/a:b/c.rs:12"] {
+            assert_eq!(FileTarget::from(target).to_string(), target);
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_unicode() {
+        let payload = "0edit_file;path=/tmp/ünï cödé/中文.rs:3;path=🦀.rs;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::EditFile(EditFileMessage {
+                paths: vec!["/tmp/ünï cödé/中文.rs:3".into(), "🦀.rs".into()],
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_unicode_version() {
+        // numeric but not a supported version
+        let message = parse_pipe_message("²edit_file;path=a;");
+
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid protocol version ², supported: '0', '1'".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_fuzz() {
+        for input in crate::tests::random_inputs(0xdecaf, 5000) {
+            for version in ["", "0", "1", "0write_to_pane;type=terminal;data="] {
+                let _ = parse_pipe_message(&format!("{version}{input}"));
+            }
+            let _ = FileTarget::from(input.as_str());
+        }
+    }
+
+    #[test]
+    fn test_parse_v0_message_round_trip_fuzz() {
+        for input in crate::tests::random_inputs(0xbeef, 5000) {
+            // v0 has no escaping, values can't contain `;`
+            let value = input.replace(';', "");
+            let payload = format!("0reveal_file;path={value};");
+
+            assert_eq!(
+                parse_pipe_message(&payload).unwrap(),
+                Message::RevealFile(RevealFileMessage {
+                    path: value,
+                    focus: None,
+                    session: None
+                }),
+                "{payload:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_invalid_version() {
+        let payload = "xnew_instance;name=test_instance;";
+        let message = parse_pipe_message(payload);
+
+        assert!(message.is_err());
+        assert_eq!(
+            message.err().unwrap(),
+            "first character must be a protocol version".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_invalid_message() {
+        let payload = "0invalid_message;name=test_instance;";
+        let message = parse_pipe_message(payload);
+
+        assert!(message.is_err());
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid protocol message invalid_message".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_missing_instance_name() {
+        let payload = "0new_instance;";
+        let message = parse_pipe_message(payload);
+
+        assert!(message.is_err());
+        assert_eq!(message.err().unwrap(), "name is required".to_string());
+    }
+
+    #[test]
+    fn test_parse_pipe_message_empty_payload() {
+        let payload = "";
+        let message = parse_pipe_message(payload);
+
+        assert!(message.is_err());
+        assert_eq!(message.err().unwrap(), "invalid payload len 0".to_string());
+    }
+
+    #[test]
+    fn test_parse_pipe_message_protocol_version_1() {
+        let payload = "1new_instance;name=test_instance;path=/tmp;";
+        let message = parse_pipe_message(payload);
+
+        // a v0 body under the v1 version is rejected, not read as v0
+        assert!(message.is_err());
+        assert!(message.err().unwrap().starts_with("invalid v1 message"));
+    }
+
+    #[test]
+    fn test_parse_pipe_message_unsupported_protocol_version() {
+        let payload = "2new_instance;name=test_instance;path=/tmp;";
+        let message = parse_pipe_message(payload);
+
+        assert!(message.is_err());
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid protocol version 2, supported: '0', '1'".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_v1_edit_file() {
+        let payload = r#"1{"command":"edit_file","paths":[{"path":"/tmp/a;b=c.txt","line":4}]}"#;
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::EditFile(EditFileMessage {
+                paths: vec![FileTarget {
+                    path: "/tmp/a;b=c.txt".into(),
+                    line: Some(4),
+                    column: None,
+                }],
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_encode_pipe_message_keeps_file_targets() {
+        // a path that reads like a position is sent as is
+        let message = Message::EditFile(EditFileMessage {
+            paths: vec![FileTarget {
+                path: "/tmp/build:1".into(),
+                line: None,
+                column: None,
+            }],
+            focus: None,
+            session: None,
+        });
+        let encoded = encode_pipe_message(&message).unwrap();
+
+        assert_eq!(
+            encoded,
+            r#"1{"command":"edit_file","paths":[{"path":"/tmp/build:1"}]}"#
+        );
+        assert_eq!(parse_pipe_message(&encoded).unwrap(), message);
+    }
+
+    #[test]
+    fn test_parse_pipe_message_v1_write_to_pane() {
+        let payload = r#"1{"command":"write_to_pane","type":"terminal","data":["escape",{"string":"a=b;c"},"enter"]}"#;
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::WriteToPane(WriteToPaneMessage {
+                typ: PaneType::Terminal,
+                data: WritesToPane(vec![
+                    WriteToPane::Escape,
+                    WriteToPane::String("a=b;c".into()),
+                    WriteToPane::Enter,
+                ]),
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_v1_invalid_json() {
+        let payload = r#"1{"command":"edit_file""#;
+        let message = parse_pipe_message(payload);
+
+        assert!(message.err().unwrap().starts_with("invalid v1 message"));
+    }
+
+    #[test]
+    fn test_parse_pipe_message_v1_missing_key() {
+        let payload = r#"1{"command":"edit_file"}"#;
+        let message = parse_pipe_message(payload);
+
+        assert!(message.err().unwrap().contains("missing field `paths`"));
+    }
+
+    #[test]
+    fn test_encode_pipe_message_round_trip() {
+        let messages = vec![
+            Message::NewInstance(NewInstanceMessage {
+                name: "with \"quotes\"".into(),
+                path: "/tmp/a=b;c".into(),
+                layout: Some("nu".into()),
+                vars: BTreeMap::from([("var.a;b".into(), "c=d".into())]),
+                force: true,
+            }),
+            Message::EditFile(EditFileMessage {
+                paths: vec!["/tmp/ünïcode;path=1".into(), "/tmp/a.rs:1:2".into()],
+                focus: None,
+                session: None,
+            }),
+            Message::Editor(
+                EditorAction::Goto {
+                    line: 1,
+                    column: None,
+                }
+                .into(),
+            ),
+            Message::Editor(
+                EditorAction::Open {
+                    paths: vec!["/tmp/a.rs:3".into()],
+                }
+                .into(),
+            ),
+            Message::Editor(EditorAction::ReloadAll.into()),
+            Message::Editor(EditorMessage {
+                action: EditorAction::SaveAll,
+                focus: Some(true),
+                session: Some(1736946000123),
+            }),
+            Message::RevealFile(RevealFileMessage {
+                path: "/tmp/a b;c.rs".into(),
+                focus: None,
+                session: Some(1736946000123),
+            }),
+            Message::FocusPane(FocusPaneMessage {
+                typ: PaneType::Custom("lazygit".into()),
+                session: None,
+            }),
+            Message::RegisterPane(RegisterPaneMessage {
+                pane: 3,
+                pid: 1234,
+                role: Some(PaneType::Custom("tests".into())),
+                session: Some(1736946000123),
+            }),
+            Message::PaneExited(PaneExitedMessage {
+                pane: 3,
+                status: 137,
+                restarting: true,
+                session: None,
+            }),
+            Message::ListSessions,
+            Message::ListPanes(ListPanesMessage { session: None }),
+            Message::ListPanes(ListPanesMessage {
+                session: Some(1736946000123),
+            }),
+            Message::LayoutEnv(LayoutEnvMessage { layout: None }),
+            Message::WriteToPane(WriteToPaneMessage {
+                typ: PaneType::FileExplorer,
+                data: WritesToPane(vec![
+                    WriteToPane::Bytes(vec![27, 91, 65]),
+                    WriteToPane::String("<not a symbol>;=".into()),
+                    WriteToPane::Enter,
+                ]),
+                focus: None,
+                session: None,
+            }),
+        ];
+
+        for message in messages {
+            let encoded = encode_pipe_message(&message).unwrap();
+            assert!(encoded.starts_with(PROTOCOL_VERSION));
+            assert_eq!(parse_pipe_message(&encoded).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_parse_pipe_message_session() {
+        let payload = "0focus_pane;type=terminal;session=1234;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::FocusPane(FocusPaneMessage {
+                typ: PaneType::Terminal,
+                session: Some(1234),
+            })
+        );
+        assert_eq!(
+            parse_pipe_message("0reveal_file;path=a;session=abc;")
+                .err()
+                .unwrap(),
+            "invalid session id: abc".to_string()
+        );
+    }
+
+    #[test]
+    fn test_session_id_range() {
+        // any id v0 accepts survives a v1 round trip
+        let message =
+            parse_pipe_message("0focus_pane;type=editor;session=18446744073709551615;").unwrap();
+        let encoded = encode_pipe_message(&message).unwrap();
+
+        assert_eq!(parse_pipe_message(&encoded).unwrap(), message);
+        assert_eq!(
+            parse_pipe_message("0focus_pane;type=editor;session=18446744073709551616;")
+                .err()
+                .unwrap(),
+            "invalid session id: 18446744073709551616".to_string()
+        );
+    }
+
+    #[test]
+    fn test_set_default_session() {
+        let mut message = parse_pipe_message("0editor;action=save_all;").unwrap();
+        message.set_default_session(1);
+        let mut targeted = parse_pipe_message("0focus_pane;type=editor;session=2;").unwrap();
+        targeted.set_default_session(1);
+        let mut list_panes = Message::ListPanes(ListPanesMessage { session: None });
+        list_panes.set_default_session(1);
+
+        assert_eq!(
+            message,
+            Message::Editor(EditorMessage {
+                action: EditorAction::SaveAll,
+                focus: None,
+                session: Some(1),
+            })
+        );
+        assert_eq!(
+            targeted,
+            Message::FocusPane(FocusPaneMessage {
+                typ: PaneType::Editor,
+                session: Some(2),
+            })
+        );
+        assert_eq!(
+            list_panes,
+            Message::ListPanes(ListPanesMessage { session: None })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_pane_lifecycle() {
+        assert_eq!(
+            parse_pipe_message("0register_pane;pane=3;pid=1234;session=7;role=editor;").unwrap(),
+            Message::RegisterPane(RegisterPaneMessage {
+                pane: 3,
+                pid: 1234,
+                role: Some(PaneType::Editor),
+                session: Some(7),
+            })
+        );
+        assert_eq!(
+            parse_pipe_message("0pane_exited;pane=3;status=-1;restarting=true;").unwrap(),
+            Message::PaneExited(PaneExitedMessage {
+                pane: 3,
+                status: -1,
+                restarting: true,
+                session: None,
+            })
+        );
+        assert_eq!(
+            parse_pipe_message("0pane_exited;pane=3;").err().unwrap(),
+            "status is required".to_string()
+        );
+        assert_eq!(
+            parse_pipe_message("0register_pane;pane=3;pid=x;")
+                .err()
+                .unwrap(),
+            "invalid pid value x, expected a number".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_list_panes() {
+        let payload = "0list_panes;session=1234;";
+        let message = parse_pipe_message(payload).unwrap();
+
+        assert_eq!(
+            message,
+            Message::ListPanes(ListPanesMessage {
+                session: Some(1234)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_layout_env() {
+        let payload = "0layout_env;layout=nu;";
+        let mut message = parse_pipe_message(payload).unwrap();
+        message.set_default_session(1);
+
+        assert_eq!(
+            message,
+            Message::LayoutEnv(LayoutEnvMessage {
+                layout: Some("nu".into())
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_message_list_panes_invalid_session() {
+        let payload = "0list_panes;session=abc;";
+        let message = parse_pipe_message(payload);
+
+        assert_eq!(
+            message.err().unwrap(),
+            "invalid session id: abc".to_string()
+        );
+    }
+
+    #[test]
+    fn test_encode_response_ok_without_data() {
+        let response = Response::from(Ok(serde_json::Value::Null));
+        let encoded = encode_response(&response).unwrap();
+
+        assert_eq!(encoded, r#"{"status":"ok"}"#);
+        assert_eq!(parse_response(&encoded).unwrap(), response);
+    }
+
+    #[test]
+    fn test_encode_response_round_trip() {
+        let responses = vec![
+            Response::Ok {
+                data: serde_json::json!({"sessions": [1, 2]}),
+            },
+            Response::from(Err("invalid instance type Editor".to_string())),
+        ];
+
+        for response in responses {
+            let encoded = encode_response(&response).unwrap();
+            assert_eq!(parse_response(&encoded).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn test_parse_response_invalid() {
+        let response = parse_response("not json");
+
+        assert!(response.err().unwrap().starts_with("invalid response"));
+    }
+
+    #[test]
+    fn test_message_from_kvs_raw_data() {
+        let kvs = [
+            ("type", "terminal"),
+            ("data", "cat < a<enter>"),
+            ("raw", "true"),
+        ];
+        let message = Message::from_kvs("write_to_pane", &kvs).unwrap();
+
+        assert_eq!(
+            message,
+            Message::WriteToPane(WriteToPaneMessage {
+                typ: PaneType::Terminal,
+                data: WritesToPane(vec![WriteToPane::String("cat < a<enter>".into())]),
+                focus: None,
+                session: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_message_from_kvs_keeps_separators_in_values() {
+        let kvs = [("type", "terminal"), ("data", "echo a=b;c<enter>")];
+        let message = Message::from_kvs("write_to_pane", &kvs).unwrap();
+
+        assert_eq!(
+            message,
+            Message::WriteToPane(WriteToPaneMessage {
+                typ: PaneType::Terminal,
+                data: WritesToPane(vec![
+                    WriteToPane::String("echo a=b;c".into()),
+                    WriteToPane::Enter,
+                ]),
+                focus: None,
+                session: None
+            })
+        );
+    }
+}
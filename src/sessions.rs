@@ -0,0 +1,727 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{InstancePane, PaneType, Result, extract_env_from_cmd};
+
+// SessionIds allocates session ids. Ids are millisecond timestamps so they don't
+// repeat across plugin reloads, bumped past the last allocated id when sessions
+// are created within the same millisecond and past ids that are already taken.
+#[derive(Debug, Default)]
+pub struct SessionIds {
+    last: u64,
+}
+
+impl SessionIds {
+    pub fn next(&mut self, now_millis: u64, is_taken: impl Fn(u64) -> bool) -> u64 {
+        let mut id = now_millis.max(self.last + 1);
+        while is_taken(id) {
+            id += 1;
+        }
+
+        self.last = id;
+        id
+    }
+}
+
+// SessionRecord is what the plugin remembers about a session it opened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub id: u64,
+    pub root: String,
+    pub layout: String,
+    // Set once panes of the session showed up, until then the session is still
+    // opening and isn't forgotten for having no panes.
+    #[serde(default)]
+    pub opened: bool,
+}
+
+// Sessions whose panes didn't show up within OPENING_TIMEOUT_MILLIS of being
+// created never opened, e.g. their layout was invalid, and are forgotten.
+const OPENING_TIMEOUT_MILLIS: u64 = 30_000;
+
+impl SessionRecord {
+    // expired tells whether the session should have opened by now_millis, ids
+    // are the millisecond timestamps the sessions were created at.
+    fn expired(&self, now_millis: u64) -> bool {
+        !self.opened && now_millis.saturating_sub(self.id) >= OPENING_TIMEOUT_MILLIS
+    }
+}
+
+// PaneRecord is what `hide-cli run` reported about the program it supervises in
+// a pane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneRecord {
+    pub id: u32,
+    pub session: Option<u64>,
+    // The pane type declared with `hide-cli run --role`, it takes precedence over
+    // the pane rules.
+    pub role: Option<PaneType>,
+    // The pid of the running program, None once it exited.
+    pub pid: Option<u32>,
+    pub exit_status: Option<i32>,
+    pub restarts: u32,
+    // When the pane was last reported, in milliseconds since the epoch.
+    #[serde(default)]
+    pub reported: u64,
+    // Set once the pane showed up in a PaneUpdate, until then it is kept even
+    // though no instance has it.
+    #[serde(default)]
+    pub seen: bool,
+}
+
+impl PaneRecord {
+    // expired tells whether the pane should have shown up by now_millis, it gets
+    // as long as a session does.
+    fn expired(&self, now_millis: u64) -> bool {
+        !self.seen && now_millis.saturating_sub(self.reported) >= OPENING_TIMEOUT_MILLIS
+    }
+}
+
+// The plugin's data dir, zellij mounts it at /data in the plugin's sandbox.
+const DEFAULT_STATE_DIR: &str = "/data";
+
+// SessionRegistry keeps the root path of every session opened through
+// new_instance, so opening the same path again can switch to it instead, and the
+// panes supervised by `hide-cli run`. It is saved to the state file of
+// the zellij session so it survives plugin reloads.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRegistry {
+    #[serde(default, rename = "sessions")]
+    records: Vec<SessionRecord>,
+    #[serde(default)]
+    panes: Vec<PaneRecord>,
+}
+
+// state_dir reads the `state_dir` plugin configuration, the directory the
+// session registries are saved in.
+pub fn state_dir(config: &BTreeMap<String, String>) -> String {
+    config
+        .get("state_dir")
+        .map(|path| path.trim().trim_end_matches('/'))
+        .filter(|path| !path.is_empty())
+        .unwrap_or(DEFAULT_STATE_DIR)
+        .into()
+}
+
+// state_file returns the path the registry of a zellij session is saved to,
+// every zellij session running the plugin keeps its own.
+pub fn state_file(state_dir: &str, session_name: &str) -> String {
+    let session_name: String = session_name
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{state_dir}/hide-state-{session_name}.json")
+}
+
+impl SessionRegistry {
+    // from_json restores a registry saved with to_json.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| format!("invalid session state: {e}"))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|e| format!("encode session state: {e}"))
+    }
+
+    pub fn insert(&mut self, id: u64, root: &str, layout: &str) {
+        self.records.push(SessionRecord {
+            id,
+            root: normalize_root(root).into(),
+            layout: layout.into(),
+            opened: false,
+        });
+    }
+
+    pub fn get(&self, id: u64) -> Option<&SessionRecord> {
+        self.records.iter().find(|record| record.id == id)
+    }
+
+    pub fn find_by_root(&self, root: &str) -> Option<&SessionRecord> {
+        let root = normalize_root(root);
+        self.records.iter().find(|record| record.root == root)
+    }
+
+    // forget_expired forgets the sessions that never opened, a restored registry
+    // holds them until the next reconcile otherwise. Returns whether any was.
+    pub fn forget_expired(&mut self, now_millis: u64) -> bool {
+        let len = self.records.len();
+        self.records.retain(|record| !record.expired(now_millis));
+        self.records.len() != len
+    }
+
+    // register_pane records the program `hide-cli run` started in pane id.
+    pub fn register_pane(
+        &mut self,
+        id: u32,
+        session: Option<u64>,
+        pid: u32,
+        role: Option<PaneType>,
+        now_millis: u64,
+    ) {
+        let pane = self.pane_mut(id);
+        pane.session = session.or(pane.session);
+        pane.pid = Some(pid);
+        pane.role = role;
+        pane.reported = now_millis;
+    }
+
+    // role returns the type declared for pane id by `hide-cli run --role`.
+    pub fn role(&self, id: u32) -> Option<&PaneType> {
+        self.pane(id).and_then(|pane| pane.role.as_ref())
+    }
+
+    // pane_exited records how the program of pane id exited, restarting when
+    // `hide-cli run` starts it again.
+    pub fn pane_exited(
+        &mut self,
+        id: u32,
+        session: Option<u64>,
+        status: i32,
+        restarting: bool,
+        now_millis: u64,
+    ) {
+        let pane = self.pane_mut(id);
+        pane.session = session.or(pane.session);
+        pane.pid = None;
+        pane.exit_status = Some(status);
+        if restarting {
+            pane.restarts += 1;
+        }
+        pane.reported = now_millis;
+    }
+
+    pub fn pane(&self, id: u32) -> Option<&PaneRecord> {
+        self.panes.iter().find(|pane| pane.id == id)
+    }
+
+    fn pane_mut(&mut self, id: u32) -> &mut PaneRecord {
+        let idx = match self.panes.iter().position(|pane| pane.id == id) {
+            Some(idx) => idx,
+            None => {
+                self.panes.push(PaneRecord {
+                    id,
+                    session: None,
+                    role: None,
+                    pid: None,
+                    exit_status: None,
+                    restarts: 0,
+                    reported: 0,
+                    seen: false,
+                });
+                self.panes.len() - 1
+            }
+        };
+        &mut self.panes[idx]
+    }
+
+    // reconcile marks sessions that have panes as opened and forgets the opened
+    // sessions whose panes are all closed, the sessions that never opened and
+    // the panes that were closed. Panes that didn't show up yet are kept like
+    // opening sessions, `hide-cli run` can report a pane before the PaneUpdate
+    // that has it. Pane ids are reused once zellij resurrects a session, panes
+    // that moved to another session are forgotten as well. Returns whether
+    // anything changed.
+    pub fn reconcile(
+        &mut self,
+        instances: &HashMap<u64, Vec<InstancePane>>,
+        now_millis: u64,
+    ) -> bool {
+        let before = self.clone();
+        self.records.retain_mut(|record| {
+            if instances.contains_key(&record.id) {
+                record.opened = true;
+            }
+            if record.opened {
+                instances.contains_key(&record.id)
+            } else {
+                !record.expired(now_millis)
+            }
+        });
+        self.panes.retain_mut(|record| {
+            let sessions: Vec<u64> = instances
+                .iter()
+                .filter(|(_, panes)| panes.iter().any(|pane| pane.info.id == record.id))
+                .map(|(session, _)| *session)
+                .collect();
+            if sessions
+                .iter()
+                .any(|session| record.session.is_none_or(|id| id == *session))
+            {
+                record.seen = true;
+            } else if !sessions.is_empty() || record.seen {
+                return false;
+            }
+            !record.expired(now_millis)
+        });
+
+        *self != before
+    }
+}
+
+// normalize_root drops trailing slashes so `/src/hide/` and `/src/hide` are the
+// same project.
+fn normalize_root(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" if path.starts_with('/') => "/",
+        root => root,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: u64,
+    pub tabs: Vec<usize>,
+    pub panes: usize,
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneSummary {
+    pub session: u64,
+    pub tab: usize,
+    pub id: u32,
+    pub title: String,
+    #[serde(rename = "type")]
+    pub typ: PaneType,
+    pub focused: bool,
+    // Set for panes running `hide-cli run`, the pid while the program runs and
+    // the status it last exited with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+}
+
+impl PaneSummary {
+    pub fn new(session: u64, pane: &InstancePane) -> Self {
+        PaneSummary {
+            session,
+            tab: pane.tab_index,
+            id: pane.info.id,
+            title: pane.info.title.clone(),
+            typ: pane.typ.clone(),
+            focused: pane.info.is_focused,
+            pid: None,
+            exit_status: None,
+        }
+    }
+
+    pub fn with_record(mut self, record: Option<&PaneRecord>) -> Self {
+        if let Some(record) = record {
+            self.pid = record.pid;
+            self.exit_status = record.exit_status;
+        }
+        self
+    }
+}
+
+pub fn summarize_sessions(
+    instances: &HashMap<u64, Vec<InstancePane>>,
+    focused_session: u64,
+) -> Vec<SessionSummary> {
+    let mut sessions: Vec<SessionSummary> = instances
+        .iter()
+        .map(|(id, panes)| {
+            let mut tabs: Vec<usize> = panes.iter().map(|p| p.tab_index).collect();
+            tabs.sort();
+            tabs.dedup();
+
+            SessionSummary {
+                id: *id,
+                tabs,
+                panes: panes.len(),
+                focused: *id == focused_session,
+            }
+        })
+        .collect();
+
+    sessions.sort_by_key(|s| s.id);
+    sessions
+}
+
+// summarize_panes lists the panes of a single session, or of all sessions
+// when none is given.
+pub fn summarize_panes(
+    instances: &HashMap<u64, Vec<InstancePane>>,
+    session: Option<u64>,
+) -> Result<Vec<PaneSummary>> {
+    let mut panes: Vec<PaneSummary> = match session {
+        Some(session) => instances
+            .get(&session)
+            .ok_or_else(|| format!("invalid session id: {session}"))?
+            .iter()
+            .map(|pane| PaneSummary::new(session, pane))
+            .collect(),
+        None => instances
+            .iter()
+            .flat_map(|(session, panes)| panes.iter().map(|pane| PaneSummary::new(*session, pane)))
+            .collect(),
+    };
+
+    panes.sort_by_key(|p| (p.session, p.tab, p.id));
+    Ok(panes)
+}
+
+// find_session_by_path returns the open session started for path, recognised by
+// the SESSION_CWD the layout sets in the commands of its panes.
+pub fn find_session_by_path(
+    instances: &HashMap<u64, Vec<InstancePane>>,
+    path: &str,
+) -> Option<u64> {
+    let path = normalize_root(path);
+    let mut sessions: Vec<u64> = instances
+        .iter()
+        .filter(|(_, panes)| {
+            panes.iter().any(|pane| {
+                pane.info.terminal_command.as_deref().is_some_and(|cmd| {
+                    extract_env_from_cmd(cmd, "SESSION_CWD").any(|cwd| normalize_root(&cwd) == path)
+                })
+            })
+        })
+        .map(|(id, _)| *id)
+        .collect();
+
+    // the oldest session when a path is open more than once
+    sessions.sort();
+    sessions.first().copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zellij_tile::prelude::PaneInfo;
+
+    fn pane(id: u32, tab_index: usize, title: &str, is_focused: bool) -> InstancePane {
+        InstancePane {
+            info: PaneInfo {
+                id,
+                title: title.into(),
+                is_focused,
+                ..Default::default()
+            },
+            tab_index,
+            typ: title.into(),
+        }
+    }
+
+    fn instances() -> HashMap<u64, Vec<InstancePane>> {
+        HashMap::from([
+            (
+                2,
+                vec![
+                    pane(5, 1, "Editor", true),
+                    pane(4, 1, "yazi", false),
+                    pane(6, 3, "Terminal", false),
+                ],
+            ),
+            (1, vec![pane(1, 0, "hx", false)]),
+        ])
+    }
+
+    #[test]
+    fn test_session_ids_same_millisecond() {
+        let mut ids = SessionIds::default();
+
+        assert_eq!(ids.next(1000, |_| false), 1000);
+        assert_eq!(ids.next(1000, |_| false), 1001);
+        assert_eq!(ids.next(1000, |_| false), 1002);
+        assert_eq!(ids.next(2000, |_| false), 2000);
+    }
+
+    #[test]
+    fn test_session_ids_clock_going_backwards() {
+        let mut ids = SessionIds::default();
+
+        assert_eq!(ids.next(2000, |_| false), 2000);
+        assert_eq!(ids.next(1000, |_| false), 2001);
+    }
+
+    #[test]
+    fn test_session_ids_skips_taken() {
+        let mut ids = SessionIds::default();
+        let taken = instances();
+
+        assert_eq!(ids.next(1, |id| taken.contains_key(&id)), 3);
+        assert_eq!(ids.next(1, |id| taken.contains_key(&id)), 4);
+    }
+
+    #[test]
+    fn test_summarize_sessions() {
+        let sessions = summarize_sessions(&instances(), 2);
+
+        assert_eq!(
+            sessions,
+            vec![
+                SessionSummary {
+                    id: 1,
+                    tabs: vec![0],
+                    panes: 1,
+                    focused: false,
+                },
+                SessionSummary {
+                    id: 2,
+                    tabs: vec![1, 3],
+                    panes: 3,
+                    focused: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summarize_panes_of_session() {
+        let panes = summarize_panes(&instances(), Some(2)).unwrap();
+
+        assert_eq!(
+            panes
+                .iter()
+                .map(|p| (p.id, p.typ.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (4, PaneType::FileExplorer),
+                (5, PaneType::Editor),
+                (6, PaneType::Terminal),
+            ]
+        );
+        assert!(panes[1].focused);
+    }
+
+    #[test]
+    fn test_summarize_panes_of_all_sessions() {
+        let panes = summarize_panes(&instances(), None).unwrap();
+
+        assert_eq!(
+            panes.iter().map(|p| (p.session, p.id)).collect::<Vec<_>>(),
+            vec![(1, 1), (2, 4), (2, 5), (2, 6)]
+        );
+    }
+
+    #[test]
+    fn test_summarize_panes_invalid_session() {
+        let panes = summarize_panes(&instances(), Some(3));
+
+        assert_eq!(panes.err().unwrap(), "invalid session id: 3".to_string());
+    }
+
+    #[test]
+    fn test_find_session_by_path() {
+        let mut instances = instances();
+        instances.get_mut(&2).unwrap()[1].info.terminal_command =
+            Some("fish -c SESSION_ID=2 SESSION_CWD=/src/hide/ yazi".into());
+        instances.insert(
+            3,
+            vec![InstancePane {
+                info: PaneInfo {
+                    terminal_command: Some("bash -c SESSION_CWD=\"/src/hide\" hx".into()),
+                    ..Default::default()
+                },
+                tab_index: 4,
+                typ: PaneType::Editor,
+            }],
+        );
+
+        assert_eq!(find_session_by_path(&instances, "/src/hide"), Some(2));
+        assert_eq!(find_session_by_path(&instances, "/src/hide/"), Some(2));
+        assert_eq!(find_session_by_path(&instances, "/src"), None);
+    }
+
+    #[test]
+    fn test_find_session_by_quoted_path() {
+        let mut instances = instances();
+        instances.get_mut(&2).unwrap()[0].info.terminal_command =
+            Some(r"fish -c SESSION_ID=2 SESSION_CWD='/src/it'\''s a '\\'hide' hx".into());
+
+        assert_eq!(
+            find_session_by_path(&instances, "/src/it's a \\hide"),
+            Some(2)
+        );
+        assert_eq!(find_session_by_path(&instances, "/src/it's"), None);
+    }
+
+    #[test]
+    fn test_session_registry_find_by_root() {
+        let mut registry = SessionRegistry::default();
+        registry.insert(1, "/src/hide/", "default");
+        registry.insert(2, "/", "nu");
+
+        assert_eq!(registry.find_by_root("/src/hide").map(|r| r.id), Some(1));
+        assert_eq!(registry.find_by_root("//").map(|r| r.id), Some(2));
+        assert_eq!(registry.get(2).unwrap().layout, "nu");
+        assert!(registry.find_by_root("/src").is_none());
+    }
+
+    #[test]
+    fn test_session_registry_reconcile() {
+        let mut registry = SessionRegistry::default();
+        registry.insert(1, "/src/hide", "default");
+        registry.insert(2, "/src/zellij", "default");
+        registry.insert(3, "/src/yazi", "default");
+
+        // 3 is still opening, it has no panes yet
+        assert!(registry.reconcile(&instances(), 4));
+        assert!(!registry.reconcile(&instances(), 4));
+        assert!(registry.get(1).unwrap().opened);
+        assert!(registry.get(2).unwrap().opened);
+        assert!(!registry.get(3).unwrap().opened);
+
+        // 1 was closed
+        let mut instances = instances();
+        instances.remove(&1);
+        registry.reconcile(&instances, 4);
+        assert!(registry.get(1).is_none());
+        assert!(registry.get(2).is_some());
+        assert!(registry.get(3).is_some());
+    }
+
+    #[test]
+    fn test_session_registry_forgets_sessions_that_never_opened() {
+        let mut registry = SessionRegistry::default();
+        registry.insert(2, "/src/zellij", "default");
+        registry.insert(1000, "/src/yazi", "default");
+
+        registry.reconcile(&instances(), 1000);
+        assert!(!registry.reconcile(&instances(), 30_999));
+        assert!(registry.get(1000).is_some());
+
+        // opened sessions don't expire, only the ones without panes do
+        assert!(registry.reconcile(&instances(), 31_000));
+        assert!(registry.get(2).is_some());
+        assert!(registry.find_by_root("/src/yazi").is_none());
+    }
+
+    #[test]
+    fn test_session_registry_forget_expired() {
+        let mut registry = SessionRegistry::default();
+        registry.insert(1, "/src/hide", "default");
+        registry.insert(2, "/src/zellij", "default");
+        registry.insert(40_000, "/src/yazi", "default");
+        registry.reconcile(&instances(), 1000);
+
+        // 1 and 2 opened, 40000 is still opening
+        assert!(!registry.forget_expired(60_000));
+        assert!(registry.forget_expired(70_000));
+        assert_eq!(registry.get(40_000), None);
+        assert!(registry.get(1).is_some());
+    }
+
+    #[test]
+    fn test_session_registry_panes() {
+        let mut registry = SessionRegistry::default();
+        registry.register_pane(5, Some(2), 100, None, 0);
+        registry.pane_exited(5, None, 101, true, 0);
+        registry.register_pane(5, None, 200, Some(PaneType::Editor), 0);
+
+        assert_eq!(
+            registry.pane(5),
+            Some(&PaneRecord {
+                id: 5,
+                session: Some(2),
+                role: Some(PaneType::Editor),
+                pid: Some(200),
+                exit_status: Some(101),
+                restarts: 1,
+                reported: 0,
+                seen: false,
+            })
+        );
+
+        registry.pane_exited(5, None, 0, false, 1000);
+        registry.pane_exited(9, None, 1, false, 1000);
+        registry.reconcile(&instances(), 31_000);
+
+        assert_eq!(registry.pane(5).unwrap().pid, None);
+        assert_eq!(registry.pane(5).unwrap().exit_status, Some(0));
+        assert_eq!(registry.role(5), Some(&PaneType::Editor));
+        assert_eq!(registry.pane(9), None);
+    }
+
+    #[test]
+    fn test_session_registry_forgets_panes_of_other_sessions() {
+        let mut registry = SessionRegistry::default();
+        registry.register_pane(5, Some(1), 100, Some(PaneType::Terminal), 0);
+        registry.register_pane(4, None, 101, None, 0);
+        registry.reconcile(&instances(), 0);
+
+        // pane 5 belongs to session 2 now
+        assert_eq!(registry.pane(5), None);
+        assert!(registry.pane(4).is_some());
+    }
+
+    #[test]
+    fn test_session_registry_keeps_panes_until_seen() {
+        let mut registry = SessionRegistry::default();
+        registry.register_pane(7, Some(2), 100, None, 1000);
+        registry.register_pane(8, Some(2), 101, None, 1000);
+
+        // neither pane showed up in a PaneUpdate yet
+        assert!(!registry.reconcile(&instances(), 2000));
+        assert!(registry.pane(7).is_some());
+
+        let mut shown = instances();
+        shown
+            .get_mut(&2)
+            .unwrap()
+            .push(pane(7, 1, "Terminal", false));
+        assert!(registry.reconcile(&shown, 2000));
+        assert!(registry.pane(7).unwrap().seen);
+
+        // once seen, a pane is forgotten as soon as it is closed
+        assert!(registry.reconcile(&instances(), 3000));
+        assert_eq!(registry.pane(7), None);
+        assert!(registry.pane(8).is_some());
+
+        // one that never showed up is forgotten after the timeout
+        assert!(!registry.reconcile(&instances(), 30_999));
+        assert!(registry.reconcile(&instances(), 31_000));
+        assert_eq!(registry.pane(8), None);
+    }
+
+    #[test]
+    fn test_session_registry_json() {
+        let mut registry = SessionRegistry::default();
+        registry.insert(1736946000123, "/src/hide", "nu");
+        registry.register_pane(5, Some(1736946000123), 100, Some(PaneType::Editor), 0);
+        registry.pane_exited(6, None, 1, false, 0);
+
+        let json = registry.to_json().unwrap();
+
+        assert_eq!(SessionRegistry::from_json(&json).unwrap(), registry);
+        assert_eq!(
+            SessionRegistry::from_json("{}").unwrap(),
+            SessionRegistry::default()
+        );
+        assert!(
+            SessionRegistry::from_json("{")
+                .err()
+                .unwrap()
+                .starts_with("invalid session state: ")
+        );
+    }
+
+    #[test]
+    fn test_state_dir() {
+        let config = BTreeMap::from([("state_dir".to_string(), " /host/.cache/ ".to_string())]);
+
+        assert_eq!(state_dir(&config), "/host/.cache");
+        assert_eq!(state_dir(&BTreeMap::new()), "/data");
+    }
+
+    #[test]
+    fn test_state_file() {
+        assert_eq!(
+            state_file("/data", "hide-dev"),
+            "/data/hide-state-hide-dev.json"
+        );
+        assert_eq!(
+            state_file("/data", "../a b/ünï"),
+            "/data/hide-state-.._a_b_ünï.json"
+        );
+    }
+}
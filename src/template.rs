@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+
+use crate::Result;
+
+// Template is a layout with `{placeholder}`s. A placeholder is a `{`, a name made
+// of ascii letters, digits, `_` and `.`, and a `}`. Every other `{` is kept as is,
+// so KDL children blocks don't need escaping.
+//
+// Placeholders are expected inside KDL strings, values are escaped accordingly.
+// `{name|shell}` is also quoted as a single shell word, for placeholders in the
+// commands of panes.
+#[derive(Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder { name: String, shell: bool },
+}
+
+const SHELL_FILTER: &str = "|shell}";
+
+impl Template {
+    pub fn parse(source: &str) -> Self {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+
+            let name_len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+
+            let end = if rest[name_len..].starts_with('}') {
+                Some((1, false))
+            } else if rest[name_len..].starts_with(SHELL_FILTER) {
+                Some((SHELL_FILTER.len(), true))
+            } else {
+                None
+            };
+
+            if let Some((end_len, shell)) = end.filter(|_| name_len > 0) {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Placeholder {
+                    name: rest[..name_len].into(),
+                    shell,
+                });
+                rest = &rest[name_len + end_len..];
+            } else {
+                text.push('{');
+            }
+        }
+
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Template { parts }
+    }
+
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder { name, .. } => Some(name.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    // env returns the names of the environment variables the `{env.NAME}`
+    // placeholders refer to, each once.
+    pub fn env(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .placeholders()
+            .filter_map(|name| name.strip_prefix("env."))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // unused returns the names no placeholder refers to, used to catch typos in
+    // user supplied variables.
+    pub fn unused<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+        names
+            .into_iter()
+            .filter(|name| !self.placeholders().any(|p| p == *name))
+            .collect()
+    }
+
+    pub fn render(&self, vars: &BTreeMap<String, String>) -> Result<String> {
+        let mut unknown: Vec<&str> = self
+            .placeholders()
+            .filter(|name| !vars.contains_key(*name))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            unknown.dedup();
+            return Err(format!(
+                "unknown layout placeholders: {}",
+                unknown.join(", ")
+            ));
+        }
+
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Placeholder { name, shell: false } => {
+                    rendered.push_str(&escape_kdl_string(&vars[name]))
+                }
+                Part::Placeholder { name, shell: true } => {
+                    rendered.push_str(&escape_kdl_string(&quote_shell(&vars[name])))
+                }
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+pub fn escape_kdl_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// quote_shell quotes value as a single word for sh, bash, zsh and fish. Inside
+// single quotes fish still treats `\` as an escape, backslashes are escaped
+// outside of the quotes instead.
+fn quote_shell(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for ch in value.chars() {
+        match ch {
+            '\'' => quoted.push_str(r"'\''"),
+            '\\' => quoted.push_str(r"'\\'"),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> BTreeMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_template_render() {
+        let template = Template::parse(r#"tab name="{tab_name}" cwd="{path}" {"#);
+        let rendered = template
+            .render(&vars(&[("tab_name", "hide"), ("path", "/tmp/hide")]))
+            .unwrap();
+
+        assert_eq!(rendered, r#"tab name="hide" cwd="/tmp/hide" {"#);
+    }
+
+    #[test]
+    fn test_template_keeps_kdl_blocks() {
+        let source = "layout {\n    pane {}\n    pane { command \"{cmd}\"; }\n}\n";
+        let template = Template::parse(source);
+
+        assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["cmd"]);
+        assert_eq!(
+            template.render(&vars(&[("cmd", "hx")])).unwrap(),
+            "layout {\n    pane {}\n    pane { command \"hx\"; }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_template_escapes_values() {
+        let template = Template::parse(r#"cwd="{path}""#);
+        let rendered = template
+            .render(&vars(&[("path", r#"/tmp/a "b" \c {path}"#)]))
+            .unwrap();
+
+        assert_eq!(rendered, r#"cwd="/tmp/a \"b\" \\c {path}""#);
+    }
+
+    #[test]
+    fn test_template_shell_quotes_values() {
+        let template = Template::parse(r#"args "-c" "CWD={path|shell} hx" "{path|sh}""#);
+
+        assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["path"]);
+        assert_eq!(
+            template
+                .render(&vars(&[("path", r#"/tmp/it's "a" \b"#)]))
+                .unwrap(),
+            r#"args "-c" "CWD='/tmp/it'\\''s \"a\" '\\\\'b' hx" "{path|sh}""#
+        );
+    }
+
+    #[test]
+    fn test_quote_shell() {
+        assert_eq!(quote_shell("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(quote_shell(""), "''");
+        assert_eq!(quote_shell(r"it's\"), r"'it'\''s'\\''");
+    }
+
+    #[test]
+    fn test_template_dotted_and_repeated_placeholders() {
+        let template = Template::parse("{env.HOME}/{var.dir}/{var.dir}");
+
+        assert_eq!(
+            template
+                .render(&vars(&[("env.HOME", "/home/me"), ("var.dir", "src")]))
+                .unwrap(),
+            "/home/me/src/src"
+        );
+    }
+
+    #[test]
+    fn test_template_env() {
+        let template = Template::parse("{env.SHELL} {path} {env.HOME}/{env.SHELL} {var.env.X}");
+
+        assert_eq!(template.env(), vec!["HOME", "SHELL"]);
+    }
+
+    #[test]
+    fn test_template_unknown_placeholders() {
+        let template = Template::parse("{tab_name} {git_branch} {var.b} {var.b}");
+        let rendered = template.render(&vars(&[("tab_name", "hide")]));
+
+        assert_eq!(
+            rendered.err().unwrap(),
+            "unknown layout placeholders: git_branch, var.b".to_string()
+        );
+    }
+
+    #[test]
+    fn test_template_unused() {
+        let template = Template::parse("{var.used} {path}");
+
+        assert_eq!(
+            template.unused(["var.used", "var.unused"]),
+            vec!["var.unused"]
+        );
+    }
+
+    #[test]
+    fn test_template_unterminated_placeholder() {
+        let template = Template::parse("{tab_name");
+
+        assert_eq!(template.placeholders().count(), 0);
+        assert_eq!(template.render(&vars(&[])).unwrap(), "{tab_name");
+    }
+}